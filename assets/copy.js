@@ -0,0 +1,13 @@
+// "Copy link" buttons copy their data-url, made absolute, to the clipboard
+document.addEventListener("click", (event) => {
+    const button = event.target.closest(".copy-link");
+    if (!button) {
+        return;
+    }
+
+    const url = new URL(button.dataset.url, location.href).href;
+    navigator.clipboard.writeText(url).then(() => {
+        button.textContent = "Copied!";
+        setTimeout(() => button.textContent = "Copy link", 2000);
+    });
+});
@@ -0,0 +1,36 @@
+// Press j/k to move between results marked with data-post, and Enter to open
+// the selected one
+let selected = -1;
+
+document.addEventListener("keydown", (event) => {
+    if (event.ctrlKey || event.metaKey || event.altKey || event.target.closest("input, textarea, select")) {
+        return;
+    }
+
+    const items = document.querySelectorAll("[data-post]");
+    if (items.length === 0) {
+        return;
+    }
+
+    let next = selected;
+    if (event.key === "j") {
+        next = Math.min(selected + 1, items.length - 1);
+    } else if (event.key === "k") {
+        next = Math.max(selected - 1, 0);
+    } else if (event.key === "Enter" && items[selected]) {
+        const link = items[selected].querySelector("a");
+        if (link) {
+            location.href = link.href;
+        }
+        return;
+    } else {
+        return;
+    }
+
+    if (items[selected]) {
+        items[selected].classList.remove("selected");
+    }
+    selected = next;
+    items[selected].classList.add("selected");
+    items[selected].scrollIntoView({block: "nearest"});
+});
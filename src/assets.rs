@@ -0,0 +1,75 @@
+//! Static assets, embedded at compile time.
+
+use rocket::{Responder, get, uri};
+use rocket::http::{ContentType, Header};
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// The contents of the assets/ directory.
+const ASSETS: &[(&str, &[u8])] = &[
+    ("copy.js", include_bytes!("../assets/copy.js")),
+    ("nav.js", include_bytes!("../assets/nav.js")),
+    ("style.css", include_bytes!("../assets/style.css")),
+];
+
+/// Get the contents of an asset.
+pub fn get(name: &str) -> Option<&'static [u8]> {
+    ASSETS.iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, data)| *data)
+}
+
+/// Get the URL for an asset.  The URL changes whenever the asset does, so it
+/// can be cached forever.
+pub fn url(name: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    get(name).hash(&mut hasher);
+    format!("{}?v={:016x}", uri!(asset(name)), hasher.finish())
+}
+
+#[derive(Responder)]
+pub struct Asset {
+    data: &'static [u8],
+    content_type: ContentType,
+    cache_control: Header<'static>,
+}
+
+#[get("/assets/<path..>")]
+pub fn asset(path: PathBuf) -> Option<Asset> {
+    let data = get(path.to_str()?)?;
+    let content_type = Path::new(&path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(ContentType::from_extension)
+        .unwrap_or(ContentType::Binary);
+
+    Some(Asset {
+        data,
+        content_type,
+        cache_control: Header::new("Cache-Control", "public, max-age=31536000, immutable"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::client;
+
+    use rocket::http::Status;
+
+    #[test]
+    fn serve_asset() {
+        let client = client(&[]);
+        let response = client.get(url("style.css")).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::CSS));
+        assert_eq!(response.headers().get_one("Cache-Control"), Some("public, max-age=31536000, immutable"));
+        assert_eq!(response.into_bytes().as_deref(), get("style.css"));
+
+        let response = client.get("/assets/nav.js").dispatch();
+        assert_eq!(response.content_type(), Some(ContentType::JavaScript));
+        assert_eq!(client.get("/assets/missing.css").dispatch().status(), Status::NotFound);
+    }
+}
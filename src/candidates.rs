@@ -0,0 +1,387 @@
+//! Candidate posts for manual review and labeling.
+
+use crate::{THEME_TOGGLE, assets, get_str, render, render_result_item, theme};
+use crate::config::Config;
+use crate::date;
+use crate::posts::{Post, Posts, Snapshot};
+use crate::query::{self, Query};
+
+use rayon::prelude::*;
+
+use rocket::{Route, State, get, routes, uri};
+use rocket::http::{ContentType, CookieJar};
+use rocket::response::Redirect;
+use rocket::response::content::Html;
+
+use serde_json::json;
+
+/// The number of buckets the candidates are split into.
+const BUCKETS: usize = 3;
+
+/// How the candidates are split into buckets.
+#[derive(Clone, Copy, PartialEq)]
+enum By {
+    /// Equal numbers of posts per bucket.
+    Count,
+    /// Equal spans of time per bucket, plus a final bucket for undated posts.
+    Date,
+}
+
+impl By {
+    fn from_param(by: Option<&str>) -> Self {
+        match by {
+            Some("date") => Self::Date,
+            _ => Self::Count,
+        }
+    }
+
+    /// The total number of buckets.
+    fn buckets(self) -> usize {
+        match self {
+            Self::Count => BUCKETS,
+            Self::Date => BUCKETS + 1,
+        }
+    }
+
+    /// The ?by= parameter to preserve this mode in links.
+    fn param(self) -> Option<&'static str> {
+        match self {
+            Self::Count => None,
+            Self::Date => Some("date"),
+        }
+    }
+}
+
+/// Find the IDs of the posts to review, sorted.  This scans every post, so
+/// use the cached [`Posts::candidates()`] instead.
+pub fn find(posts: &Posts, fields: &[&str]) -> Vec<String> {
+    let queries = vec![
+        "degree",
+        "career", "careers",
+        "programming",
+        "school",
+        "learn", "learning",
+        "switch", "switching",
+        "change", "changing",
+        "college", "university",
+        "advice",
+        "bootcamp", "bootcamps", "camp", "camps",
+        "self taught",
+    ];
+
+    let query = Query::Or(queries.iter().map(|q| query::parse(q)).collect());
+
+    let mut matches: Vec<_> = posts.par_iter()
+        .filter(|post| query.matches_in(post, fields))
+        .map(|post| get_str(post, "id").to_string())
+        .collect();
+
+    matches.sort();
+    matches
+}
+
+/// Get the candidate posts, sorted by ID.
+fn find_candidates<'a>(posts: &'a Posts, config: &Config) -> Vec<&'a Post> {
+    posts.candidates(&config.text_fields())
+        .iter()
+        .filter_map(|id| posts.get(id))
+        .collect()
+}
+
+/// Get the candidates in the nth bucket, and a heading describing them.
+fn bucket<'a>(matches: &[&'a Post], n: usize, by: By, locale: &str) -> (Vec<&'a Post>, String) {
+    let count = render::format_count(matches.len(), locale);
+
+    if by == By::Count {
+        let len = matches.len();
+        let end = ((n + 1) * len / BUCKETS).min(len);
+        let start = (n * len / BUCKETS).min(end);
+        let heading = format!(
+            "Candidates {}–{} of {}",
+            render::format_count(start, locale),
+            render::format_count(end, locale),
+            count,
+        );
+        return (matches[start..end].to_vec(), heading);
+    }
+
+    let created = |post: &Post| post.get("created_utc").and_then(date::timestamp);
+
+    if n == BUCKETS {
+        let undated: Vec<_> = matches.iter()
+            .copied()
+            .filter(|post| created(post).is_none())
+            .collect();
+        let heading = format!("Undated candidates ({} of {})", render::format_count(undated.len(), locale), count);
+        return (undated, heading);
+    }
+
+    let times = matches.iter().filter_map(|post| created(post));
+    let (min, max) = match (times.clone().min(), times.max()) {
+        (Some(min), Some(max)) => (min, max),
+        _ => return (vec![], format!("No dated candidates (0 of {})", count)),
+    };
+
+    // Split [min, max] into equal spans, the last of which includes max
+    let span = (max - min + 1) as i128;
+    let from = min + (n as i128 * span / BUCKETS as i128) as i64;
+    let to = min + ((n + 1) as i128 * span / BUCKETS as i128) as i64;
+
+    let mut posts: Vec<_> = matches.iter()
+        .copied()
+        .filter(|post| created(post).is_some_and(|t| from <= t && t < to))
+        .collect();
+    posts.sort_by_key(|post| created(post));
+
+    let heading = format!(
+        "Candidates from {} to {} ({} of {})",
+        date::format_date(from),
+        date::format_date(to - 1),
+        render::format_count(posts.len(), locale),
+        count,
+    );
+    (posts, heading)
+}
+
+/// Navigation between candidate buckets.
+fn candidates_nav(n: Option<usize>, by: By) -> String {
+    let mode = by.param();
+    let mut output = String::from(r#"<nav class="pager">"#);
+    if let Some(current) = n {
+        if current > 0 {
+            output += &format!(r#"<a href="{}">« Prev</a> "#, uri!(candidates(current - 1, mode)));
+        }
+        if current + 1 < by.buckets() {
+            output += &format!(r#"<a href="{}">Next »</a> "#, uri!(candidates(current + 1, mode)));
+        }
+        output += &format!(r#"<a href="{}">Export</a> "#, uri!(export_candidates(current, mode)));
+    }
+
+    let hidden = match mode {
+        Some(mode) => format!(r#"<input type="hidden" name="by" value="{}">"#, mode),
+        None => String::new(),
+    };
+    output += &format!(r#"
+        <form action="{}" method="get">
+            <label for="bucket">Jump to bucket: </label>
+            <input type="number" name="n" id="bucket" min="0" max="{}" required>
+            {}
+            <input type="submit" value="Go">
+        </form>
+    </nav>"#, uri!(jump_candidates(_, _)), by.buckets() - 1, hidden);
+    output
+}
+
+#[get("/candidates/jump?<n>&<by>")]
+fn jump_candidates(config: &State<Config>, cookies: &CookieJar<'_>, n: Option<&str>, by: Option<&str>) -> Result<Redirect, Html<String>> {
+    let mode = by;
+    let by = By::from_param(mode);
+    let n = n.unwrap_or("");
+    match n.trim().parse::<usize>() {
+        Ok(bucket) if bucket < by.buckets() => Ok(Redirect::to(uri!(candidates(bucket, mode)))),
+        _ => Err(Html(format!(r#"<!DOCTYPE HTML>
+            <html{theme}>
+                <head>
+                    <title>Pheddit Candidates</title>
+                    <meta name="viewport" content="width=device-width, initial-scale=1">
+                    <link rel="stylesheet" type="text/css" href="{style}">
+                    {header}
+                </head>
+                <body>
+                    {toggle}
+                    <h2>No such bucket</h2>
+                    <p>“{n}” isn't a bucket number.  Pick a number from 0 to {max}.</p>
+                    {nav}
+                    {footer}
+                </body>
+            </html>
+        "#, theme=theme(cookies), style=assets::url("style.css"), toggle=THEME_TOGGLE, n=render::escape(n), max=by.buckets() - 1, nav=candidates_nav(None, by), header=config.header, footer=config.footer))),
+    }
+}
+
+#[get("/candidates/<n>?<by>")]
+fn candidates(posts: Snapshot, config: &State<Config>, cookies: &CookieJar<'_>, n: usize, by: Option<&str>) -> Option<Html<String>> {
+    let by = By::from_param(by);
+    if n >= by.buckets() {
+        return None;
+    }
+
+    let matches = find_candidates(&posts, config);
+    let (bucket, heading) = bucket(&matches, n, by, &config.locale);
+
+    let mut output = format!(r#"<!DOCTYPE HTML>
+        <html{theme}>
+            <head>
+                <title>Pheddit Candidates | {n}/{buckets}</title>
+                <meta name="viewport" content="width=device-width, initial-scale=1">
+                <link rel="stylesheet" type="text/css" href="{style}">
+                <script src="{script}" defer></script>
+                {header}
+            </head>
+            <body>
+                {toggle}
+                <h2>{heading}</h2>
+    "#, theme=theme(cookies), style=assets::url("style.css"), script=assets::url("nav.js"), toggle=THEME_TOGGLE, n=n, buckets=by.buckets(), heading=heading, header=config.header);
+
+    // Small corpora can leave some buckets empty
+    if bucket.is_empty() {
+        output += r#"<p class="notice">No candidates in this bucket.</p>"#;
+    } else {
+        output += "<ul>";
+        for post in bucket {
+            output += &render_result_item(post, None);
+        }
+        output += "</ul>";
+    }
+
+    output += &candidates_nav(Some(n), by);
+    output += &config.footer;
+    output += r#"
+            </body>
+        </html>
+    "#;
+
+    Some(Html(output))
+}
+
+/// Export a bucket of candidates as NDJSON, for importing into a labeling tool.
+#[get("/candidates/<n>/export.jsonl?<by>")]
+fn export_candidates(posts: Snapshot, config: &State<Config>, n: usize, by: Option<&str>) -> Option<(ContentType, String)> {
+    let by = By::from_param(by);
+    if n >= by.buckets() {
+        return None;
+    }
+
+    let matches = find_candidates(&posts, config);
+    let (bucket, _) = bucket(&matches, n, by, &config.locale);
+
+    let mut output = String::new();
+    for post in bucket {
+        let line = json!({
+            "id": get_str(post, "id"),
+            "title": get_str(post, "title"),
+            "url": get_str(post, "url"),
+        });
+        output += &line.to_string();
+        output += "\n";
+    }
+
+    Some((ContentType::new("application", "x-ndjson"), output))
+}
+
+/// All the candidate review routes.
+pub fn routes() -> Vec<Route> {
+    routes![candidates, jump_candidates, export_candidates]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    use rocket::http::Status;
+
+    use serde_json::Value;
+
+    /// Seconds per day.
+    const DAY: i64 = 24 * 60 * 60;
+
+    /// Some candidate posts, with IDs c0, c1, ....
+    fn fixtures(n: usize) -> Vec<Value> {
+        (0..n).map(|i| fixture(&format!("c{}", i), &format!("Career question {}", i), "")).collect()
+    }
+
+    #[test]
+    fn export() {
+        let mut posts = fixtures(6);
+        posts.push(fixture("x", "Unrelated", ""));
+        let client = client(&posts);
+
+        let response = client.get("/candidates/1/export.jsonl").dispatch();
+        assert_eq!(response.content_type(), Some(ContentType::new("application", "x-ndjson")));
+        let lines: Vec<Value> = response.into_string()
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["id"], "c2");
+        assert_eq!(lines[1]["title"], "Career question 3");
+    }
+
+    #[test]
+    fn jump() {
+        let client = client(&fixtures(3));
+        let response = client.get("/candidates/jump?n=2").dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        assert_eq!(response.headers().get_one("Location"), Some("/candidates/2"));
+        let response = client.get("/candidates/jump?n=3&by=date").dispatch();
+        assert_eq!(response.headers().get_one("Location"), Some("/candidates/3?by=date"));
+
+        for n in ["3", "-1", "x", ""] {
+            let response = client.get(format!("/candidates/jump?n={}", n)).dispatch();
+            assert_eq!(response.status(), Status::Ok, "{}", n);
+            assert!(response.into_string().unwrap().contains("No such bucket"), "{}", n);
+        }
+        assert_eq!(client.get("/candidates/3").dispatch().status(), Status::NotFound);
+    }
+
+    #[test]
+    fn date_buckets() {
+        let mut posts = fixtures(5);
+        for (post, time) in posts.iter_mut().zip([0, 50 * DAY, 100 * DAY, 250 * DAY]) {
+            post["created_utc"] = json!(time);
+        }
+        let client = client(&posts);
+
+        let ids = |n: usize| -> Vec<String> {
+            get(&client, &format!("/candidates/{}/export.jsonl?by=date", n))
+                .lines()
+                .map(|line| serde_json::from_str::<Value>(line).unwrap()["id"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(ids(0), ["c0", "c1"]);
+        assert_eq!(ids(1), ["c2"]);
+        assert_eq!(ids(2), ["c3"]);
+        assert_eq!(ids(3), ["c4"]);
+
+        let page = get(&client, "/candidates/1?by=date");
+        assert!(page.contains("Candidates from 1970-03-25 to 1970-06-16 (1 of 5)"), "{}", page);
+        let page = get(&client, "/candidates/3?by=date");
+        assert!(page.contains("Undated candidates (1 of 5)"));
+    }
+
+    #[test]
+    fn empty_buckets() {
+        let client = client(&fixtures(2));
+        let pages: Vec<_> = (0..BUCKETS).map(|n| get(&client, &format!("/candidates/{}", n))).collect();
+        assert!(pages[0].contains("No candidates in this bucket."));
+        for page in &pages[1..] {
+            assert_eq!(page.matches("<li data-post").count(), 1);
+            assert!(!page.contains("No candidates in this bucket."));
+        }
+        assert!(get(&client, "/candidates/3?by=date").contains("Undated candidates (2 of 2)"));
+    }
+
+    #[test]
+    fn result_items() {
+        let mut posts = fixtures(3);
+        posts[2]["link_flair_text"] = json!("Student");
+        let client = client(&posts);
+
+        // The item for c2, up to the end of its flair badge
+        let item = |page: &str| -> Option<String> {
+            let start = page.find(r#"<li data-post="c2">"#)?;
+            let end = start + page[start..].find("</span>")? + "</span>".len();
+            Some(page[start..end].to_string())
+        };
+        let candidate = (0..BUCKETS)
+            .find_map(|n| item(&get(&client, &format!("/candidates/{}", n))))
+            .unwrap();
+        assert_eq!(candidate, r#"<li data-post="c2"><a href="/post/c2">Career question 2</a> <span class="flair">Student</span>"#);
+
+        // Search results only differ by highlighting the query on the post
+        let result = item(&get(&client, "/search?query=career")).unwrap();
+        assert_eq!(result.replace("?highlight=career", ""), candidate);
+    }
+}
@@ -0,0 +1,164 @@
+//! Settings read from the environment at startup.
+
+use crate::get_body;
+
+use rocket::figment::Figment;
+
+use serde_json::Value;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Read an environment variable, falling back to a default if it's unset or
+/// invalid.
+fn var<T: FromStr>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            eprintln!("warning: ignoring invalid {}={}", name, value);
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+/// Read an HTML fragment from the file named by an environment variable.
+fn fragment(name: &str) -> String {
+    let path = match env::var_os(name) {
+        Some(path) => path,
+        None => return String::new(),
+    };
+    fs::read_to_string(&path).unwrap_or_else(|e| {
+        eprintln!("warning: couldn't read {}={}: {}", name, path.to_string_lossy(), e);
+        String::new()
+    })
+}
+
+/// Parse a comma-separated list of fields, falling back to the title and body.
+fn text_fields(list: &str) -> Vec<String> {
+    let fields: Vec<_> = list.split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(String::from)
+        .collect();
+
+    if fields.is_empty() {
+        vec!["title".to_string(), "selftext".to_string()]
+    } else {
+        fields
+    }
+}
+
+pub struct Config {
+    /// Query words shorter than this are ignored (PHEDDIT_MIN_TERM_LEN).
+    pub min_term_len: usize,
+    /// The default number of search results per page (PHEDDIT_PER_PAGE).
+    pub per_page: usize,
+    /// Where candidate labels are recorded (PHEDDIT_LABELS).
+    pub labels: PathBuf,
+    /// Post bodies longer than this many bytes are truncated (PHEDDIT_MAX_BODY).
+    pub max_body: usize,
+    /// Trusted HTML to add to every page's <head> (from the file PHEDDIT_HEADER).
+    pub header: String,
+    /// Trusted HTML to add to the end of every page's <body> (PHEDDIT_FOOTER).
+    pub footer: String,
+    /// The public URL of the site, for absolute links (PHEDDIT_BASE_URL).
+    pub base_url: String,
+    /// The shared secret for the /admin endpoints, which are disabled if it's
+    /// unset (PHEDDIT_ADMIN_TOKEN).
+    pub admin_token: Option<String>,
+    /// Render HTML embedded in post bodies as-is, instead of escaping it
+    /// (PHEDDIT_ALLOW_RAW_HTML=true).  Only enable this for trusted archives.
+    pub allow_raw_html: bool,
+    /// Render posts from Reddit's pre-rendered, sanitized `selftext_html` when
+    /// it's there, instead of the `selftext` markdown
+    /// (PHEDDIT_PREFER_SELFTEXT_HTML=true).
+    pub prefer_selftext_html: bool,
+    /// The post fields that searches match, title first (PHEDDIT_TEXT_FIELDS).
+    pub text_fields: Vec<String>,
+    /// /api/search only returns this many of the best results (PHEDDIT_MAX_API_RESULTS).
+    pub max_api_results: usize,
+    /// Match hyphenated query words whether they're written with a hyphen or
+    /// as separate words, and apostrophes whether they're straight, curly, or
+    /// missing (PHEDDIT_LOOSE_HYPHENS=true).
+    pub loose_hyphens: bool,
+    /// The locale for formatting numbers, like en-US (PHEDDIT_LOCALE).
+    pub locale: String,
+    /// The TLS certificate chain to serve HTTPS with (PHEDDIT_TLS_CERT or
+    /// --tls-cert).  Needs a key too.
+    pub tls_cert: Option<PathBuf>,
+    /// The TLS private key (PHEDDIT_TLS_KEY or --tls-key).
+    pub tls_key: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        Self {
+            min_term_len: var("PHEDDIT_MIN_TERM_LEN", 2),
+            per_page: var("PHEDDIT_PER_PAGE", 50),
+            labels: var("PHEDDIT_LABELS", PathBuf::from("labels.jsonl")),
+            max_body: var("PHEDDIT_MAX_BODY", 1 << 20),
+            header: fragment("PHEDDIT_HEADER"),
+            footer: fragment("PHEDDIT_FOOTER"),
+            base_url: var("PHEDDIT_BASE_URL", String::new()).trim_end_matches('/').to_string(),
+            admin_token: env::var("PHEDDIT_ADMIN_TOKEN").ok().filter(|token| !token.is_empty()),
+            allow_raw_html: var("PHEDDIT_ALLOW_RAW_HTML", false),
+            prefer_selftext_html: var("PHEDDIT_PREFER_SELFTEXT_HTML", false),
+            text_fields: text_fields(&var("PHEDDIT_TEXT_FIELDS", String::from("title,selftext"))),
+            max_api_results: var("PHEDDIT_MAX_API_RESULTS", 10000),
+            loose_hyphens: var("PHEDDIT_LOOSE_HYPHENS", false),
+            locale: var("PHEDDIT_LOCALE", String::from("en-US")),
+            tls_cert: env::var_os("PHEDDIT_TLS_CERT").filter(|path| !path.is_empty()).map(PathBuf::from),
+            tls_key: env::var_os("PHEDDIT_TLS_KEY").filter(|path| !path.is_empty()).map(PathBuf::from),
+        }
+    }
+
+    /// Rocket's configuration, which serves HTTPS if there's a TLS certificate
+    /// and key, or plain HTTP if there's neither.
+    pub fn figment(&self) -> Result<Figment, &'static str> {
+        let figment = rocket::Config::figment();
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => Ok(figment.merge(("tls.certs", cert)).merge(("tls.key", key))),
+            (None, None) => Ok(figment),
+            (Some(_), None) => Err("a TLS certificate needs a key too (--tls-key or PHEDDIT_TLS_KEY)"),
+            (None, Some(_)) => Err("a TLS key needs a certificate too (--tls-cert or PHEDDIT_TLS_CERT)"),
+        }
+    }
+
+    /// The post fields that searches match.
+    pub fn text_fields(&self) -> Vec<&str> {
+        self.text_fields.iter().map(String::as_str).collect()
+    }
+
+    /// The text of a post's body, from the first non-empty text field other
+    /// than the title.
+    pub fn body<'a>(&self, post: &'a Value) -> &'a str {
+        get_body(post, &self.text_fields())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tls() {
+        let mut config = crate::testing::config();
+        config.tls_cert = None;
+        config.tls_key = None;
+        let figment = config.figment().unwrap();
+        assert!(figment.find_value("tls.certs").is_err());
+
+        config.tls_cert = Some(PathBuf::from("/etc/pheddit/cert.pem"));
+        assert!(config.figment().is_err());
+
+        config.tls_key = Some(PathBuf::from("/etc/pheddit/key.pem"));
+        let figment = config.figment().unwrap();
+        assert_eq!(figment.extract_inner::<PathBuf>("tls.certs").unwrap(), PathBuf::from("/etc/pheddit/cert.pem"));
+        assert_eq!(figment.extract_inner::<PathBuf>("tls.key").unwrap(), PathBuf::from("/etc/pheddit/key.pem"));
+
+        config.tls_cert = None;
+        assert!(config.figment().is_err());
+    }
+}
@@ -0,0 +1,89 @@
+//! Formatting for Unix timestamps.
+
+use serde_json::Value;
+
+/// Parse a timestamp, which Pushshift stores as an integer, float, or string.
+pub fn timestamp(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64().or_else(|| n.as_f64().map(|f| f as i64)),
+        Value::String(s) => s.parse::<f64>().ok().map(|f| f as i64),
+        _ => None,
+    }
+}
+
+/// Convert days since the Unix epoch to a (year, month, day) date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+/// Convert a (year, month, day) date to days since the Unix epoch.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Get the start of the day, week (from Monday), or month containing a
+/// timestamp, or None for any other unit.
+pub fn truncate(secs: i64, unit: &str) -> Option<i64> {
+    let days = secs.div_euclid(86400);
+    let start = match unit {
+        "day" => days,
+        // The epoch was a Thursday
+        "week" => days - (days + 3).rem_euclid(7),
+        "month" => {
+            let (year, month, _) = civil_from_days(days);
+            days_from_civil(year, month, 1)
+        }
+        _ => return None,
+    };
+    Some(start * 86400)
+}
+
+/// Format a timestamp as a date, like 2021-09-30.
+pub fn format_date(secs: i64) -> String {
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Format a timestamp as a date and time, like 2021-09-30 14:05 UTC.
+pub fn format_datetime(secs: i64) -> String {
+    let time = secs.rem_euclid(86400);
+    format!("{} {:02}:{:02} UTC", format_date(secs), time / 3600, time % 3600 / 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_dates() {
+        // Thursday, 2021-09-30 15:00 UTC
+        let time = 1633014000;
+        let day = |unit| truncate(time, unit).map(format_date);
+        assert_eq!(day("day").as_deref(), Some("2021-09-30"));
+        assert_eq!(day("week").as_deref(), Some("2021-09-27"));
+        assert_eq!(day("month").as_deref(), Some("2021-09-01"));
+        assert_eq!(day("year"), None);
+        assert_eq!(truncate(-1, "month").map(format_date).as_deref(), Some("1969-12-01"));
+        for days in [-800, 0, 59, 18900, 20000] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+}
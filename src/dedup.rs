@@ -0,0 +1,184 @@
+//! Finding posts with nearly identical bodies, like reposts and crossposts.
+
+use crate::{get_body, get_str, query};
+use crate::posts::{Post, Posts};
+
+use rayon::prelude::*;
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// The number of hash functions in a signature.
+const HASHES: usize = 64;
+
+/// Signatures are split into bands of this many hashes, and only posts that
+/// agree on a whole band are compared when looking for clusters.
+const ROWS: usize = 4;
+
+/// The number of consecutive words in a shingle.
+const SHINGLE: usize = 3;
+
+/// Bodies shorter than this many words are never duplicates, since short
+/// texts (and placeholders like "[removed]") all look alike.
+const MIN_WORDS: usize = 20;
+
+/// The estimated similarity at which posts count as duplicates.
+const THRESHOLD: f64 = 0.8;
+
+/// A MinHash signature of a post body: the smallest of each of several hashes
+/// of its word shingles.  The fraction of hashes two signatures share
+/// estimates how many shingles the texts share.
+pub type Signature = [u32; HASHES];
+
+/// The SplitMix64 finalizer, to derive the hash functions from one hash.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// Compute the signature of a text, unless it's too short.
+fn signature(text: &str) -> Option<Signature> {
+    let words: Vec<_> = query::words(text).collect();
+    if words.len() < MIN_WORDS {
+        return None;
+    }
+
+    let mut signature = [u32::MAX; HASHES];
+    for shingle in words.windows(SHINGLE) {
+        let mut hasher = DefaultHasher::new();
+        shingle.hash(&mut hasher);
+        let hash = hasher.finish();
+        for (i, min) in signature.iter_mut().enumerate() {
+            let h = mix(hash.wrapping_add(i as u64)) as u32;
+            *min = (*min).min(h);
+        }
+    }
+    Some(signature)
+}
+
+/// Estimate the similarity of two texts from their signatures, from 0 to 1.
+fn similarity(a: &Signature, b: &Signature) -> f64 {
+    let same = a.iter().zip(b).filter(|(a, b)| a == b).count();
+    same as f64 / HASHES as f64
+}
+
+/// Compute the signatures of all the post bodies that are long enough, by ID,
+/// taking the bodies from the given text fields.  This scans every post, so
+/// use the cached [`Posts::signatures()`] instead.
+pub fn signatures(posts: &Posts, fields: &[&str]) -> HashMap<String, Signature> {
+    posts.par_iter()
+        .filter_map(|post| {
+            let signature = signature(get_body(post, fields))?;
+            Some((get_str(post, "id").to_string(), signature))
+        })
+        .collect()
+}
+
+/// Find the other posts with nearly the same body as a post, most similar
+/// first, with their estimated similarity.
+pub fn duplicates<'a>(posts: &'a Posts, fields: &[&str], id: &str) -> Vec<(&'a Post, f64)> {
+    let signatures = posts.signatures(fields);
+    let signature = match signatures.get(id) {
+        Some(signature) => signature,
+        None => return vec![],
+    };
+
+    let mut duplicates: Vec<_> = signatures.par_iter()
+        .filter(|(other, _)| *other != id)
+        .map(|(other, s)| (other, similarity(signature, s)))
+        .filter(|&(_, similarity)| similarity >= THRESHOLD)
+        .filter_map(|(other, similarity)| Some((posts.get(other)?, similarity)))
+        .collect();
+
+    duplicates.sort_by(|(a, x), (b, y)| y.total_cmp(x).then_with(|| get_str(a, "id").cmp(get_str(b, "id"))));
+    duplicates
+}
+
+/// Find the root of a set in a union-find forest.
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Group the posts into clusters of likely duplicates, each sorted by ID.
+/// Posts without duplicates are left out.
+pub fn clusters(posts: &Posts, fields: &[&str]) -> Vec<Vec<String>> {
+    let mut signatures: Vec<_> = posts.signatures(fields).iter().collect();
+    signatures.sort_by_key(|(id, _)| *id);
+
+    // Posts that agree on every hash of some band are candidate duplicates
+    let mut parents: Vec<_> = (0..signatures.len()).collect();
+    for band in 0..HASHES / ROWS {
+        let rows = band * ROWS..(band + 1) * ROWS;
+        let mut buckets: HashMap<_, Vec<usize>> = HashMap::new();
+        for (i, (_, signature)) in signatures.iter().enumerate() {
+            buckets.entry(&signature[rows.clone()]).or_default().push(i);
+        }
+
+        for bucket in buckets.values().filter(|bucket| bucket.len() > 1) {
+            for (n, &i) in bucket.iter().enumerate() {
+                for &j in &bucket[n + 1..] {
+                    if similarity(signatures[i].1, signatures[j].1) >= THRESHOLD {
+                        let (a, b) = (find(&mut parents, i), find(&mut parents, j));
+                        parents[a.max(b)] = a.min(b);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut clusters: HashMap<_, Vec<String>> = HashMap::new();
+    for (i, (id, _)) in signatures.iter().enumerate() {
+        let root = find(&mut parents, i);
+        clusters.entry(root).or_default().push(id.to_string());
+    }
+
+    let mut clusters: Vec<_> = clusters.into_values()
+        .filter(|cluster| cluster.len() > 1)
+        .collect();
+    clusters.sort();
+    clusters
+}
+
+/// Log the clusters of likely duplicate posts.
+pub fn report(posts: &Posts, fields: &[&str]) {
+    let clusters = clusters(posts, fields);
+    for cluster in &clusters {
+        eprintln!("likely duplicates: {}", cluster.join(", "));
+    }
+    eprintln!("Found {} clusters of likely duplicate posts", clusters.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    const FIELDS: &[&str] = &["title", "selftext"];
+
+    #[test]
+    fn near_duplicates() {
+        let text = "I have been teaching myself to program for about a year now and I am wondering whether I should go back to school for a degree or keep building projects";
+        let corpus = corpus(&[
+            fixture("a", "Degree or projects?", text),
+            fixture("b", "Repost: degree or projects?", &format!("{} instead", text)),
+            fixture("c", "Unrelated", "Does anyone know a good keyboard for programming that is quiet enough for an open office and does not cost a fortune please let me know thanks"),
+            fixture("d", "Short", "degree or projects"),
+        ]);
+        let posts = corpus.posts();
+
+        let ids = |id: &str| -> Vec<_> {
+            duplicates(&posts, FIELDS, id).iter().map(|(post, _)| get_str(post, "id")).collect()
+        };
+        assert_eq!(ids("a"), ["b"]);
+        assert_eq!(ids("b"), ["a"]);
+        assert!(ids("c").is_empty());
+        assert!(ids("d").is_empty());
+        assert_eq!(clusters(&posts, FIELDS), [["a", "b"]]);
+    }
+}
@@ -0,0 +1,109 @@
+//! Exporting the archive as static HTML.
+
+use crate::{PostId, assets, get_str, render, render_post};
+use crate::config::Config;
+use crate::posts::Posts;
+
+use rayon::prelude::*;
+
+use rocket::request::FromParam;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How often to report progress, in posts.
+const PROGRESS_INTERVAL: usize = 1000;
+
+/// Wrap a page body in a standalone HTML document.
+fn page(title: &str, body: &str) -> String {
+    format!(r#"<!DOCTYPE HTML>
+        <html>
+            <head>
+                <meta charset="utf-8">
+                <title>Pheddit | {title}</title>
+                <meta name="viewport" content="width=device-width, initial-scale=1">
+                <link rel="stylesheet" type="text/css" href="style.css">
+            </head>
+            <body>
+                {body}
+            </body>
+        </html>
+    "#, title=render::escape(title), body=body)
+}
+
+/// Write every post to `dir` as a static HTML page, plus an index.html linking
+/// to them all.  Links are relative, so the export can be browsed from disk.
+pub fn export(posts: &Posts, dir: &Path, config: &Config) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join("style.css"), assets::get("style.css").unwrap_or_default())?;
+
+    let mut all: Vec<_> = posts.par_iter().collect();
+    all.sort_by_key(|post| get_str(post, "id"));
+
+    // Skip posts that don't render, or whose IDs aren't safe file names
+    let total = all.len();
+    let done = AtomicUsize::new(0);
+    let written = all.par_iter()
+        .map(|post| {
+            let id = get_str(post, "id");
+            let body = render_post(post, None, false, usize::MAX, config)
+                .filter(|_| PostId::from_param(id).is_ok());
+            if let Some(body) = &body {
+                let body = format!(r#"<nav><a href="index.html">« All posts</a></nav>{}"#, body);
+                fs::write(dir.join(format!("{}.html", id)), page(get_str(post, "title"), &body))?;
+            }
+
+            // Skipped posts count too, so the progress reaches the total
+            let n = done.fetch_add(1, Ordering::Relaxed) + 1;
+            if n.is_multiple_of(PROGRESS_INTERVAL) {
+                eprintln!("Exported {}/{} posts...", n, total);
+            }
+            Ok(body.is_some())
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let all: Vec<_> = all.into_iter()
+        .zip(written)
+        .filter_map(|(post, ok)| ok.then_some(post))
+        .collect();
+
+    let mut index = format!("<h1>Pheddit archive</h1>\n<p>{} posts</p>\n<ul>\n", all.len());
+    for post in &all {
+        index += &format!(
+            r#"<li><a href="{id}.html">{title}</a>"#,
+            id=render::escape_attr(get_str(post, "id")),
+            title=render::escape(get_str(post, "title")),
+        );
+        index += "\n";
+    }
+    index += "</ul>";
+    fs::write(dir.join("index.html"), page("Archive", &index))?;
+
+    eprintln!("Exported {} posts to {}", all.len(), dir.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn two_posts() {
+        let corpus = corpus(&[fixture("a", "First", "Hello"), fixture("b", "Second", "World")]);
+        let dir = TempDir::new();
+        export(&corpus.posts(), dir.path(), &config()).unwrap();
+
+        let index = fs::read_to_string(dir.path().join("index.html")).unwrap();
+        assert!(index.contains(r#"<a href="a.html">First</a>"#));
+        assert!(index.contains(r#"<a href="b.html">Second</a>"#));
+        for (id, body) in [("a", "Hello"), ("b", "World")] {
+            let page = fs::read_to_string(dir.path().join(format!("{}.html", id))).unwrap();
+            assert!(page.contains(body));
+            assert!(page.contains(r#"<a href="index.html">"#));
+        }
+        assert!(dir.path().join("style.css").exists());
+    }
+}
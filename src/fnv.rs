@@ -0,0 +1,81 @@
+//! A stable hash for IDs that outlive the process.
+
+/// A 64-bit FNV-1a hash.  Unlike [`DefaultHasher`], it's the same in every
+/// process and Rust version, so IDs made from it can be compared across
+/// restarts and servers.  Values are written as explicit bytes, rather than
+/// through [`Hash`], whose output isn't stable either.
+///
+/// [`DefaultHasher`]: std::collections::hash_map::DefaultHasher
+/// [`Hash`]: std::hash::Hash
+pub struct Fnv(u64);
+
+impl Fnv {
+    pub fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    /// Hash some bytes.
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    /// Hash a number.
+    pub fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    /// Hash a string.  Strings are terminated by a byte that can't appear in
+    /// UTF-8, so ("ab", "c") and ("a", "bc") hash differently.
+    pub fn write_str(&mut self, s: &str) {
+        self.write(s.as_bytes());
+        self.write(&[0xff]);
+    }
+
+    /// Hash a string that may be missing, distinctly from an empty one.
+    pub fn write_opt_str(&mut self, s: Option<&str>) {
+        match s {
+            Some(s) => {
+                self.write(&[1]);
+                self.write_str(s);
+            }
+            None => self.write(&[0]),
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_values() {
+        let hash = |bytes: &[u8]| {
+            let mut hash = Fnv::new();
+            hash.write(bytes);
+            hash.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf29ce484222325);
+        assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn separators() {
+        let strs = |strs: &[Option<&str>]| {
+            let mut hash = Fnv::new();
+            for &s in strs {
+                hash.write_opt_str(s);
+            }
+            hash.finish()
+        };
+        assert_ne!(strs(&[Some("ab"), Some("c")]), strs(&[Some("a"), Some("bc")]));
+        assert_ne!(strs(&[None]), strs(&[Some("")]));
+    }
+}
@@ -0,0 +1,52 @@
+//! Counts of past search queries, for suggesting them again.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// The most distinct queries to remember.
+const MAX_QUERIES: usize = 10000;
+
+/// How often each search query has been run, since the server started.
+#[derive(Default)]
+pub struct History {
+    counts: Mutex<HashMap<String, usize>>,
+}
+
+/// Normalize a query so differently spaced or capitalized copies count as one.
+fn normalize(query: &str) -> String {
+    query.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl History {
+    /// Count a search for a query.  Once the history is full, only queries
+    /// that have been seen before are counted.
+    pub fn record(&self, query: &str) {
+        let query = normalize(query);
+        if query.is_empty() {
+            return;
+        }
+
+        let mut counts = self.counts.lock().unwrap();
+        if let Some(count) = counts.get_mut(&query) {
+            *count += 1;
+        } else if counts.len() < MAX_QUERIES {
+            counts.insert(query, 1);
+        }
+    }
+
+    /// The most frequent past queries starting with a prefix, most first.
+    pub fn suggest(&self, prefix: &str, limit: usize) -> Vec<(String, usize)> {
+        let prefix = normalize(prefix);
+        let counts = self.counts.lock().unwrap();
+        let mut matches: Vec<_> = counts.iter()
+            .filter(|(query, _)| query.starts_with(&prefix))
+            .map(|(query, &count)| (query.clone(), count))
+            .collect();
+        matches.sort_by(|(a, m), (b, n)| n.cmp(m).then(a.cmp(b)));
+        matches.truncate(limit);
+        matches
+    }
+}
@@ -1,197 +1,1024 @@
-use pulldown_cmark::{Parser, html};
+mod assets;
+mod candidates;
+mod config;
+mod date;
+mod dedup;
+mod export;
+mod fnv;
+mod history;
+mod posts;
+mod query;
+mod render;
+mod stop_words;
+#[cfg(test)]
+mod testing;
+
+use config::Config;
+use fnv::Fnv;
+use history::History;
+use posts::{Corpus, Post, Posts, Snapshot};
+use query::Query;
 
 use rayon::prelude::*;
 
-use regex::{self, Regex};
+use regex::Regex;
 
-use rocket::{State, get, launch, routes};
-use rocket::response::content::{Css, Html};
+use rocket::{Build, Data, FromForm, Responder, Rocket, State, catch, catchers, get, launch, routes};
+use rocket::data::ByteUnit;
+use rocket::figment::Figment;
+use rocket::http::{ContentType, Cookie, CookieJar, Header, RawStr, Status};
+use rocket::request::{FromParam, FromRequest, Outcome, Request};
+use rocket::response::Redirect;
+use rocket::response::content::{Html, Json};
+use rocket::response::stream::TextStream;
 
-use serde_json::{Value, from_str};
+use serde_json::{Value, from_str, json};
 
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::fs::{self, File};
-use std::io::{BufRead, BufReader};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The `data-theme` attribute for the <html> element, if the user picked one.
+pub(crate) fn theme(cookies: &CookieJar<'_>) -> &'static str {
+    match cookies.get("theme").map(|c| c.value()) {
+        Some("light") => r#" data-theme="light""#,
+        Some("dark") => r#" data-theme="dark""#,
+        _ => "",
+    }
+}
+
+pub(crate) const THEME_TOGGLE: &str = r#"<nav class="theme">Theme: <a href="/theme/light">light</a> · <a href="/theme/dark">dark</a> · <a href="/theme/auto">auto</a></nav>"#;
+
+/// The path of the page that linked to us, if it's on this site, so /theme can
+/// send the user back.
+struct Referer(Option<String>);
+
+/// Get the path of a referring URL, if it's on the given host.
+fn local_path(referer: &str, host: Option<&str>) -> Option<String> {
+    let rest = referer.strip_prefix("https://").or_else(|| referer.strip_prefix("http://"))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/"),
+    };
+
+    // Browsers treat //host and /\host as links to other sites
+    let local = Some(authority) == host && !path.starts_with("//") && !path.starts_with("/\\");
+    local.then(|| path.to_string())
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Referer {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, ()> {
+        let host = req.headers().get_one("Host");
+        let path = req.headers().get_one("Referer").and_then(|referer| local_path(referer, host));
+        Outcome::Success(Referer(path))
+    }
+}
+
+#[get("/theme/<name>")]
+fn set_theme(cookies: &CookieJar<'_>, referer: Referer, name: &str) -> Redirect {
+    match name {
+        "light" | "dark" => cookies.add(Cookie::new("theme", name.to_string())),
+        _ => cookies.remove(Cookie::named("theme")),
+    }
 
-#[derive(Default)]
-struct Posts {
-    map: HashMap<String, Value>,
+    Redirect::to(referer.0.unwrap_or_else(|| "/".to_string()))
 }
 
 #[get("/")]
-fn index() -> Html<&'static str> {
-    Html(r#"<!DOCTYPE HTML>
-        <html>
+fn index(posts: Snapshot, config: &State<Config>, cookies: &CookieJar<'_>) -> Html<String> {
+    let banner = if posts.len() == 0 {
+        r#"<p class="notice">No posts are loaded — check your input paths.</p>"#
+    } else {
+        ""
+    };
+
+    Html(format!(r#"<!DOCTYPE HTML>
+        <html{theme}>
             <head>
                 <title>Pheddit</title>
-                <link rel="stylesheet" type="text/css" href="/style.css">
+                <meta name="viewport" content="width=device-width, initial-scale=1">
+                <link rel="stylesheet" type="text/css" href="{style}">
+                {header}
             </head>
             <body>
+                {toggle}
                 <h1>Pheddit search engine</h1>
+                {banner}
                 <form action="/search" method="get">
                     <label for="query">Query: </label>
                     <input type="search" name="query" id="query" required>
                     <input type="submit" value="Search">
                 </form>
+                {footer}
             </body>
         </html>
-    "#)
+    "#, theme=theme(cookies), style=assets::url("style.css"), toggle=THEME_TOGGLE, banner=banner, header=config.header, footer=config.footer))
+}
+
+pub(crate) fn get_str<'a>(value: &'a Value, key: &str) -> &'a str {
+    value.get(key)
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+}
+
+/// Get the text of a post's body, from the first non-empty text field other
+/// than the title.
+pub(crate) fn get_body<'a>(post: &'a Value, fields: &[&str]) -> &'a str {
+    fields.iter()
+        .filter(|&&field| field != "title")
+        .map(|field| get_str(post, field))
+        .find(|text| !text.is_empty())
+        .unwrap_or("")
+}
+
+/// Check whether a post is in one of a comma-separated list of subreddits.
+fn in_subreddits(post: &Value, subreddits: &str) -> bool {
+    let subreddit = get_str(post, "subreddit");
+    subreddits.split(',').any(|s| s.trim().eq_ignore_ascii_case(subreddit))
+}
+
+/// A badge for a post's flair, if it has any.
+fn flair_badge(post: &Value) -> String {
+    match get_str(post, "link_flair_text").trim() {
+        "" => String::new(),
+        flair => format!(r#" <span class="flair">{}</span>"#, render::escape(flair)),
+    }
+}
+
+/// Render the start of a search result or candidate list item, linking to the
+/// post and highlighting the query there, if any.  Callers can add more to the
+/// item, like a snippet.
+pub(crate) fn render_result_item(post: &Value, highlight: Option<&str>) -> String {
+    let id = get_str(post, "id");
+    let mut href = format!("/post/{}", id);
+    if let Some(query) = highlight {
+        href += &format!("?highlight={}", RawStr::new(query).percent_encode());
+    }
+
+    format!(
+        r#"<li data-post="{id}"><a href="{href}">{title}</a>{flair}"#,
+        id=render::escape_attr(id),
+        href=render::escape_attr(&href),
+        title=render::escape(get_str(post, "title")),
+        flair=flair_badge(post),
+    )
+}
+
+/// The query string for /search.
+#[derive(Default, FromForm)]
+struct SearchParams<'r> {
+    query: &'r str,
+    sort: Option<&'r str>,
+    min_match: Option<usize>,
+    mode: Option<&'r str>,
+    lang: Option<&'r str>,
+    scope: Option<&'r str>,
+    source: Option<&'r str>,
+    flair: Option<&'r str>,
+    subreddit: Option<&'r str>,
+    author: Option<&'r str>,
+    fallback: Option<&'r str>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+    snippet_len: Option<usize>,
+    fragments: Option<usize>,
 }
 
-#[get("/style.css")]
-fn style() -> Css<&'static str> {
-    Css(r#"
-        html {
-            height: 100%;
-            background: lightgray;
-            overflow-y: scroll;
+/// The most search results that can be shown on one page.
+const MAX_PER_PAGE: usize = 500;
+
+/// The default and maximum length of a result snippet, in characters.
+const SNIPPET_LEN: usize = 200;
+const MAX_SNIPPET_LEN: usize = 1000;
+
+/// The most snippet fragments that can be shown per result.
+const MAX_FRAGMENTS: usize = 5;
+
+impl SearchParams<'_> {
+    /// Get the current page number, the number of pages, and the range of
+    /// results on the current page.
+    fn paginate(&self, config: &Config, count: usize) -> (usize, usize, Range<usize>) {
+        let per_page = self.per_page.unwrap_or(config.per_page).clamp(1, MAX_PER_PAGE);
+        let pages = count.div_ceil(per_page).max(1);
+        let page = self.page.unwrap_or(1).clamp(1, pages);
+        let start = (page - 1) * per_page;
+        let end = (start + per_page).min(count);
+        (page, pages, start..end)
+    }
+
+    /// Make a highlighted snippet of a post body.
+    fn snippet(&self, text: &str, re: Option<&Regex>) -> String {
+        let len = self.snippet_len.unwrap_or(SNIPPET_LEN).clamp(1, MAX_SNIPPET_LEN);
+        let fragments = self.fragments.unwrap_or(1).clamp(1, MAX_FRAGMENTS);
+        render::snippet(text, re, len, fragments)
+    }
+
+    /// A short ID for the results of this search of a snapshot of the posts,
+    /// so people can check that they saw the same thing.  Paging and snippet
+    /// settings don't affect it.
+    fn fingerprint(&self, generation: u64) -> String {
+        let mut hash = Fnv::new();
+        let words: Vec<_> = self.query.split_whitespace().collect();
+        hash.write_u64(words.len() as u64);
+        for word in words {
+            hash.write_str(word);
+        }
+
+        let params = [
+            self.sort,
+            self.mode,
+            self.lang,
+            self.scope,
+            self.source,
+            self.flair,
+            self.subreddit,
+            self.author,
+            self.fallback,
+        ];
+        for param in params {
+            hash.write_opt_str(param);
+        }
+        match self.min_match {
+            Some(n) => {
+                hash.write(&[1]);
+                hash.write_u64(n as u64);
+            }
+            None => hash.write(&[0]),
         }
+        hash.write_u64(generation);
+        format!("{:012x}", hash.finish() >> 16)
+    }
 
-        body {
-            display: flow-root;
-            min-height: 100%;
-            max-width: 800px;
-            margin: 0 auto;
-            padding: 0 1em;
-            background: white;
-            box-shadow: 5px 0 5px gray, -5px 0 5px gray;
+    /// The URL for another page of these search results.
+    fn page_url(&self, page: usize) -> String {
+        let mut url = format!("/search?query={}", RawStr::new(self.query).percent_encode());
+        let params = [
+            ("sort", self.sort),
+            ("mode", self.mode),
+            ("lang", self.lang),
+            ("scope", self.scope),
+            ("source", self.source),
+            ("flair", self.flair),
+            ("subreddit", self.subreddit),
+            ("author", self.author),
+            ("fallback", self.fallback),
+        ];
+        for (name, value) in params {
+            if let Some(value) = value {
+                url += &format!("&{}={}", name, RawStr::new(value).percent_encode());
+            }
+        }
+        if let Some(n) = self.min_match {
+            url += &format!("&min_match={}", n);
+        }
+        let numbers = [
+            ("per_page", self.per_page),
+            ("snippet_len", self.snippet_len),
+            ("fragments", self.fragments),
+        ];
+        for (name, value) in numbers {
+            if let Some(n) = value {
+                url += &format!("&{}={}", name, n);
+            }
         }
-    "#)
+        url += &format!("&page={}", page);
+        url
+    }
 }
 
-fn get_str<'a, 'b>(value: &'a Value, key: &'b str) -> &'a str {
-    value.get(key)
-        .and_then(|v| v.as_str())
-        .unwrap_or("")
+/// The number of related terms to suggest on the results page.
+const RELATED_TERMS: usize = 8;
+
+/// The most matches to scan for related terms.
+const RELATED_SAMPLE: usize = 1000;
+
+/// Find the words that occur in the most matching posts, other than the words
+/// of the query itself.
+fn related_terms(matches: &[&Post], fields: &[&str], query: &str, stop_words: &[&str], min_len: usize) -> Vec<String> {
+    let own: HashSet<_> = query::words(query).collect();
+
+    let mut counts = HashMap::new();
+    for post in matches.iter().take(RELATED_SAMPLE) {
+        let words: HashSet<_> = fields.iter()
+            .flat_map(|field| query::words(get_str(post, field)))
+            .filter(|word| word.chars().count() >= min_len && !stop_words.contains(&word.as_str()))
+            .filter(|word| !own.contains(word))
+            .collect();
+        for word in words {
+            *counts.entry(word).or_insert(0) += 1;
+        }
+    }
+
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|(a, m), (b, n)| n.cmp(m).then(a.cmp(b)));
+    counts.into_iter()
+        .take(RELATED_TERMS)
+        .map(|(word, _)| word)
+        .collect()
 }
 
-#[get("/search?<query>")]
-fn search(posts: &State<Posts>, query: &str) -> Html<String> {
-    let mut res = vec![];
-    for word in query.split_whitespace() {
-        if let Ok(re) = Regex::new(&format!(r"(?i)\b{}\b", regex::escape(word))) {
-            res.push(re);
+/// Render a pager like "« 1 … 3 4 [5] 6 7 … 48 »" for a search.
+fn pager(params: &SearchParams<'_>, page: usize, pages: usize) -> String {
+    let link = |n: usize, text: &str| {
+        format!(r#"<a href="{}">{}</a>"#, render::escape_attr(&params.page_url(n)), text)
+    };
+
+    let mut output = format!(r#"<nav class="pager"><span>Page {} of {}</span> "#, page, pages);
+    if page > 1 {
+        output += &link(page - 1, "« Prev");
+        output += " ";
+    }
+
+    let start = page.saturating_sub(2).max(1);
+    let end = (page + 2).min(pages);
+    if start > 1 {
+        output += &link(1, "1");
+        output += if start > 2 { " … " } else { " " };
+    }
+    for n in start..=end {
+        if n == page {
+            output += &format!("<strong>{}</strong> ", n);
+        } else {
+            output += &link(n, &n.to_string());
+            output += " ";
         }
     }
+    if end < pages {
+        output += if end < pages - 1 { "… " } else { "" };
+        output += &link(pages, &pages.to_string());
+        output += " ";
+    }
 
-    let matches: Vec<_> = posts.map.par_iter()
-        .map(|(_id, post)| (post, get_str(post, "title"), get_str(post, "selftext")))
-        .filter(|(_post, title, text)| res.iter().all(|re| re.is_match(title) || re.is_match(text)))
-        .map(|(post, _title, _text)| post)
-        .collect();
+    if page < pages {
+        output += &link(page + 1, "Next »");
+    }
+    output += "</nav>";
+    output
+}
+
+/// The outcome of running a search.
+struct Results<'a> {
+    /// The query as it was matched, after dropping stop words etc.
+    query: Query,
+    /// The matching posts, in order.
+    matches: Vec<&'a Post>,
+    /// Whether the query was nothing but stop words.
+    all_stop_words: bool,
+    /// Whether nothing matched every term, so we matched any term instead.
+    any_terms: bool,
+    /// Whether the query had nothing to search for, so nothing matched.
+    empty_query: bool,
+}
+
+/// A key to sort search results by.
+#[derive(Clone, Copy)]
+enum SortKey {
+    Relevance,
+    Title,
+    Id,
+    Subreddit,
+    Score,
+    Comments,
+    Date,
+}
+
+/// Parse a list of sort keys like `subreddit,score:desc`.  Each key has a
+/// direction suffix of `:asc` or `:desc`; without one, relevance, score,
+/// comments, and date sort descending and the rest ascending.  Returns each key
+/// with whether it's descending, defaulting to relevance.
+fn sort_keys(sort: &str) -> Vec<(SortKey, bool)> {
+    let mut keys = vec![];
+    for item in sort.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        let (name, dir) = match item.split_once(':') {
+            Some((name, dir)) => (name, Some(dir)),
+            None => (item, None),
+        };
+
+        let key = match name {
+            "relevance" => SortKey::Relevance,
+            "title" => SortKey::Title,
+            "id" => SortKey::Id,
+            "subreddit" => SortKey::Subreddit,
+            "score" => SortKey::Score,
+            "comments" | "num_comments" => SortKey::Comments,
+            "date" => SortKey::Date,
+            _ => {
+                eprintln!("warning: ignoring unknown sort key '{}'", name);
+                continue;
+            }
+        };
+
+        let desc = match dir {
+            Some("asc") => false,
+            Some("desc") => true,
+            None => matches!(key, SortKey::Relevance | SortKey::Score | SortKey::Comments | SortKey::Date),
+            Some(dir) => {
+                eprintln!("warning: ignoring unknown sort direction '{}'", dir);
+                continue;
+            }
+        };
+
+        keys.push((key, desc));
+    }
+
+    if keys.is_empty() {
+        keys.push((SortKey::Relevance, true));
+    }
+    keys
+}
+
+/// Stably sort posts by a key, with posts that don't have one last.
+fn sort_posts<K: Ord>(posts: &mut [&Post], desc: bool, key: impl Fn(&Post) -> Option<K>) {
+    if desc {
+        posts.sort_by_cached_key(|post| key(post).map_or((true, None), |k| (false, Some(Reverse(k)))));
+    } else {
+        posts.sort_by_cached_key(|post| key(post).map_or((true, None), |k| (false, Some(k))));
+    }
+}
+
+impl<'a> Results<'a> {
+    /// Keep only the `n` most relevant matches, still in their sorted order.
+    fn keep_most_relevant(&mut self, n: usize, fields: &[&str]) {
+        if self.matches.len() <= n {
+            return;
+        }
+
+        let mut best = self.matches.clone();
+        best.sort_by_key(|post| get_str(post, "id"));
+        sort_posts(&mut best, true, |post| Some(self.query.score_in(post, fields)));
+        let best: HashSet<_> = best[..n].iter().map(|post| get_str(post, "id")).collect();
+        self.matches.retain(|post| best.contains(get_str(post, "id")));
+    }
+
+    /// Count the matches in each subreddit, most first.
+    fn subreddit_counts(&self) -> Vec<(&'a str, usize)> {
+        let mut counts = HashMap::new();
+        for post in &self.matches {
+            let subreddit = get_str(post, "subreddit");
+            if !subreddit.is_empty() {
+                *counts.entry(subreddit).or_insert(0) += 1;
+            }
+        }
+
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by(|(a, m), (b, n)| n.cmp(m).then(a.cmp(b)));
+        counts
+    }
+}
+
+/// The post fields a search matches.
+fn search_fields<'a>(config: &'a Config, params: &SearchParams<'_>) -> Vec<&'a str> {
+    if params.scope == Some("title") {
+        vec!["title"]
+    } else {
+        config.text_fields()
+    }
+}
+
+/// Find and sort the posts matching a search.
+fn run_search<'a>(posts: &'a Posts, config: &Config, params: &SearchParams<'_>) -> Results<'a> {
+    let &SearchParams { query, sort, min_match, mode, lang, source, flair, subreddit, author, fallback, .. } = params;
+    let lang = lang.unwrap_or("en");
+    let mut parsed = match mode {
+        Some("literal") => query::literal(query),
+        _ if config.loose_hyphens => query::parse(query).loosen_hyphens(),
+        _ => query::parse(query),
+    };
+
+    // Queries of nothing but spaces or punctuation have nothing to match
+    if parsed.is_empty() {
+        return Results {
+            query: parsed,
+            matches: vec![],
+            all_stop_words: false,
+            any_terms: false,
+            empty_query: true,
+        };
+    }
+
+    // Stop words match almost everything, so drop them.  But if the query is
+    // nothing but stop words, search for them anyway rather than matching all
+    // posts, and say so.
+    let mut all_stop_words = false;
+    if mode != Some("literal") {
+        match stop_words::for_lang(lang) {
+            Some(stop_words) => {
+                let filtered = parsed.clone().without_stop_words(stop_words);
+                if filtered.is_empty() {
+                    all_stop_words = !parsed.is_empty();
+                } else {
+                    parsed = filtered;
+                }
+            }
+            None => eprintln!("warning: no stop words for language '{}'", lang),
+        }
+    }
+
+    // Very short words match almost everything, so skip them unless they're all we have
+    let min_len = config.min_term_len;
+    let long = parsed.clone().without_words(&|word| word.chars().count() < min_len);
+    if !long.is_empty() {
+        parsed = long;
+    }
+    if let Some(n) = min_match {
+        parsed = parsed.at_least(n);
+    }
+
+    let fields = search_fields(config, params);
+    let find = |parsed: &Query| -> Vec<_> {
+        posts.par_iter()
+            .filter(|post| source.is_none_or(|s| post.is_from(s)))
+            .filter(|post| flair.is_none_or(|f| get_str(post, "link_flair_text").eq_ignore_ascii_case(f)))
+            .filter(|post| subreddit.is_none_or(|s| in_subreddits(post, s)))
+            .filter(|post| author.is_none_or(|a| get_str(post, "author").eq_ignore_ascii_case(a.trim())))
+            .filter(|post| parsed.matches_in(post, &fields))
+            .collect()
+    };
+    let mut matches = find(&parsed);
+
+    // With fallback=any, if nothing matches all the terms, try any of them.
+    // Filters like -word still have to match, so only the terms are relaxed.
+    let mut any_terms = false;
+    if matches.is_empty() && fallback == Some("any") && parsed.term_count() > 1 {
+        parsed = parsed.at_least(1);
+        matches = find(&parsed);
+        any_terms = true;
+    }
+
+    // The parallel scan returns matches in no particular order, so sort by ID
+    // first to make results (and pages) stable.  The sorts below are stable too,
+    // so ties stay in ID order.
+    matches.sort_by_key(|post| get_str(post, "id"));
+
+    // Sorting by each key in turn, from last to first, sorts by all of them
+    for &(key, desc) in sort_keys(sort.unwrap_or("")).iter().rev() {
+        match key {
+            // Case-insensitive, with untitled posts last
+            SortKey::Title => sort_posts(&mut matches, desc, |post| {
+                let title = get_str(post, "title");
+                (!title.is_empty()).then(|| (title.to_lowercase(), title.to_string()))
+            }),
+            SortKey::Subreddit => sort_posts(&mut matches, desc, |post| {
+                let subreddit = get_str(post, "subreddit");
+                (!subreddit.is_empty()).then(|| subreddit.to_lowercase())
+            }),
+            SortKey::Id => sort_posts(&mut matches, desc, |post| Some(get_str(post, "id").to_string())),
+            SortKey::Relevance => sort_posts(&mut matches, desc, |post| Some(parsed.score_in(post, &fields))),
+            SortKey::Score => sort_posts(&mut matches, desc, |post| post.get("score").and_then(Value::as_i64)),
+            SortKey::Comments => sort_posts(&mut matches, desc, |post| post.get("num_comments").and_then(Value::as_i64)),
+            SortKey::Date => sort_posts(&mut matches, desc, |post| post.get("created_utc").and_then(date::timestamp)),
+        }
+    }
+
+    Results {
+        query: parsed,
+        matches,
+        all_stop_words,
+        any_terms,
+        empty_query: false,
+    }
+}
+
+/// A search results page, with its fingerprint in the X-Pheddit-Query-Id header.
+#[derive(Responder)]
+struct SearchPage {
+    page: Html<String>,
+    query_id: Header<'static>,
+}
+
+#[get("/search?<params..>")]
+fn search(posts: Snapshot, config: &State<Config>, history: &State<History>, cookies: &CookieJar<'_>, params: SearchParams<'_>) -> SearchPage {
+    history.record(params.query);
+    let results = run_search(&posts, config, &params);
+    let facets = results.subreddit_counts();
+    let Results { query: parsed, matches, all_stop_words, any_terms, empty_query } = results;
+    let re = parsed.highlighter();
+    let SearchParams { query, lang, .. } = params;
+    let lang = lang.unwrap_or("en");
+    let min_len = config.min_term_len;
 
     let mut output = format!(r#"<!DOCTYPE HTML>
-        <html>
+        <html{theme}>
             <head>
                 <title>Pheddit Search | {query}</title>
-                <link rel="stylesheet" type="text/css" href="/style.css">
+                <meta name="viewport" content="width=device-width, initial-scale=1">
+                <link rel="stylesheet" type="text/css" href="{style}">
+                <script src="{script}" defer></script>
+                {header}
             </head>
-            <body style="max-width: 800px; margin: auto;">
+            <body>
+                {toggle}
                 <h2>{count} results for <em>{query}</em></h2>
+    "#, theme=theme(cookies), style=assets::url("style.css"), script=assets::url("nav.js"), toggle=THEME_TOGGLE, query=render::escape(query), count=render::format_count(matches.len(), &config.locale), header=config.header);
+
+    if all_stop_words {
+        output += r#"
+                <p class="notice">Your query only contains very common words, so results may not be specific.  Try adding more distinctive terms.</p>
+        "#;
+    }
+
+    if any_terms {
+        output += r#"
+                <p class="notice">No posts matched all terms; showing posts matching any term.</p>
+        "#;
+    }
+
+    let stop_words = stop_words::for_lang(lang).unwrap_or_default();
+    let related = related_terms(&matches, &config.text_fields(), query, stop_words, min_len);
+    if !related.is_empty() {
+        output += r#"<p class="related">Related terms:"#;
+        // AND binds tighter than OR, so group the query before adding to it
+        let base = if query.split_whitespace().any(|word| word == "OR") {
+            format!("({})", query)
+        } else {
+            query.to_string()
+        };
+        for term in related {
+            let refined = format!("{} {}", base, term);
+            let url = SearchParams { query: &refined, ..params }.page_url(1);
+            output += &format!(
+                r#" <a href="{}">{}</a>"#,
+                render::escape_attr(&url),
+                render::escape(&term),
+            );
+        }
+        output += "</p>";
+    }
+
+    // Let results spanning several subreddits be narrowed down to one
+    if facets.len() > 1 {
+        output += r#"<aside class="facets"><h3>Subreddits</h3><ul>"#;
+        for (subreddit, count) in facets {
+            let url = SearchParams { subreddit: Some(subreddit), ..params }.page_url(1);
+            output += &format!(
+                r#"<li><a href="{}">r/{}</a> ({})</li>"#,
+                render::escape_attr(&url),
+                render::escape(subreddit),
+                render::format_count(count, &config.locale),
+            );
+        }
+        output += "</ul></aside>";
+    }
+
+    output += r#"
                 <ul>
-    "#, query=query, count=matches.len());
+    "#;
+
+    let (page, pages, range) = params.paginate(config, matches.len());
 
-    for post in matches {
-        let id = get_str(post, "id");
-        let title = get_str(post, "title");
-        output += &format!(r#"<li><a href="/post/{id}">{title}</a>"#, id=id, title=title);
+    let empty = matches.is_empty();
+    for post in &matches[range] {
+        output += &render_result_item(post, Some(query));
+
+        let text = config.body(post);
+        if !text.is_empty() {
+            output += &format!(r#"<p class="snippet">{}</p>"#, params.snippet(text, re.as_ref()));
+        }
     }
 
     output += r#"
                 </ul>
+    "#;
+
+    if pages > 1 {
+        output += &pager(&params, page, pages);
+    }
+
+    if empty_query {
+        output += r#"
+                <div class="no-results">
+                    <p>Your query has no words to search for.  Enter some words or "quoted phrases":</p>
+                    <form action="/search" method="get">
+                        <label for="query">Query: </label>
+                        <input type="search" name="query" id="query" required>
+                        <input type="submit" value="Search">
+                    </form>
+                </div>
+        "#;
+    } else if empty {
+        output += r#"
+                <div class="no-results">
+                    <p>No posts matched your search. You could try:</p>
+                    <ul>
+                        <li>Using fewer or broader terms</li>
+                        <li>Removing quotes around phrases</li>
+                        <li>Checking the spelling of each word</li>
+                    </ul>
+                    <p><a href="/">Start a new search</a></p>
+                </div>
+        "#;
+    }
+
+    let query_id = params.fingerprint(posts.generation());
+    output += &format!(r#"<p class="source">Query ID: <code>{}</code></p>"#, query_id);
+
+    output += &config.footer;
+    output += r#"
             </body>
         </html>
     "#;
 
-    Html(output)
+    SearchPage {
+        page: Html(output),
+        query_id: Header::new("X-Pheddit-Query-Id", query_id),
+    }
 }
 
-#[get("/post/<id>")]
-fn post(posts: &State<Posts>, id: &str) -> Option<Html<String>> {
-    let post = posts.map.get(id)?;
+/// A well-formed (base-36) Reddit post ID.
+pub(crate) struct PostId<'a>(&'a str);
+
+impl<'a> FromParam<'a> for PostId<'a> {
+    type Error = &'a str;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        let valid = !param.is_empty()
+            && param.bytes().all(|b| matches!(b, b'a'..=b'z' | b'0'..=b'9' | b'_'));
+        if valid {
+            Ok(PostId(param))
+        } else {
+            Err(param)
+        }
+    }
+}
+
+/// Render the body of a post page, from its title through its text, which
+/// comes from the configured text fields.  Bodies longer than `max_body` bytes
+/// are truncated, and HTML in them is escaped unless the config allows raw
+/// HTML.  With `prefer_selftext_html`, the body is rendered from Reddit's own
+/// HTML when the post has it.
+pub(crate) fn render_post(post: &Post, re: Option<&Regex>, debug: bool, max_body: usize, config: &Config) -> Option<String> {
     let title = post.get("title")?.as_str()?;
-    let text = post.get("selftext")?.as_str()?;
+    let text = config.body(post);
 
-    let mut output = format!(r#"<!DOCTYPE HTML>
-        <html>
+    // Crossposts have no body of their own, so show the original post instead
+    let parent = post.get("crosspost_parent_list")
+        .and_then(Value::as_array)
+        .and_then(|parents| parents.first())
+        .filter(|parent| text.is_empty() && parent.is_object());
+    let mut text = parent.map_or(text, |parent| config.body(parent));
+
+    let truncated = text.len() > max_body;
+    if truncated {
+        let mut end = max_body;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text = &text[..end];
+    }
+
+    let mut output = format!(r#"
+                <h1>{title}</h1>
+                {flair}
+    "#, title=render::escape(title), flair=flair_badge(post));
+
+    let subreddit = get_str(post, "subreddit");
+    if !subreddit.is_empty() {
+        output += &format!(
+            r#"<p class="subreddit"><a href="https://www.reddit.com/r/{href}">r/{name}</a></p>"#,
+            href=RawStr::new(subreddit).percent_encode(),
+            name=render::escape(subreddit),
+        );
+    }
+
+    if let Some(created) = post.get("created_utc").and_then(date::timestamp) {
+        output += &format!(r#"<p class="date">Posted {}"#, date::format_date(created));
+        // Reddit's "edited" is false, or when it was edited (or true for some old posts)
+        match post.get("edited") {
+            Some(Value::Bool(true)) => output += " (edited)",
+            Some(edited) => {
+                if let Some(time) = date::timestamp(edited) {
+                    output += &format!(r#" <span title="{}">(edited)</span>"#, date::format_datetime(time));
+                }
+            }
+            None => {}
+        }
+        output += "</p>";
+    }
+
+    output += &format!(r#"<p class="source">Source: {}</p>"#, render::escape(&post.source));
+    if debug {
+        let file = post.file.to_string_lossy();
+        output += &format!(r#"<p class="source">File: {}</p>"#, render::escape(&file));
+
+        // When the archive captured the post, which can be long after it was made
+        let retrieved = post.get("retrieved_utc")
+            .or_else(|| post.get("retrieved_on"))
+            .and_then(date::timestamp);
+        if let Some(retrieved) = retrieved {
+            output += &format!(r#"<p class="source">Archived: {}</p>"#, date::format_datetime(retrieved));
+        }
+    }
+
+    // Link posts point somewhere else
+    if post.get("is_self").and_then(|v| v.as_bool()) == Some(false) {
+        let url = get_str(post, "url");
+        if !url.is_empty() {
+            output += &format!(
+                r#"<p class="link"><a href="{href}">{url}</a></p>"#,
+                href=render::escape_attr(url),
+                url=render::escape(url),
+            );
+        }
+    }
+
+    if let Some(parent) = parent {
+        let subreddit = get_str(parent, "subreddit");
+        let from = if subreddit.is_empty() {
+            "another post".to_string()
+        } else {
+            format!(
+                r#"<a href="https://www.reddit.com/r/{href}">r/{name}</a>"#,
+                href=RawStr::new(subreddit).percent_encode(),
+                name=render::escape(subreddit),
+            )
+        };
+        output += &format!(r#"<p class="crosspost">Crossposted from {}</p>"#, from);
+        output += &format!("<h2>{}</h2>", render::escape(get_str(parent, "title")));
+    }
+
+    // Reddit's HTML is the whole body, so only use it if we're showing it all
+    let html = match get_str(post, "selftext_html") {
+        html if config.prefer_selftext_html && parent.is_none() && !truncated => html,
+        _ => "",
+    };
+
+    // Reddit replaces the body with a placeholder when it goes away
+    match text {
+        "[removed]" => output += r#"<p class="removed">This post was removed.</p>"#,
+        "[deleted]" => output += r#"<p class="removed">This post was deleted by its author.</p>"#,
+        _ if !html.is_empty() => output += &render::sanitize_html(&render::unescape(html), re),
+        _ => output += &render::markdown(text, re, config.allow_raw_html),
+    }
+
+    if truncated {
+        output += &format!(
+            r#"<p class="notice">This post is too long to show in full.  <a href="/api/posts/{}/selftext">Download the full text</a>.</p>"#,
+            render::escape_attr(get_str(post, "id")),
+        );
+    }
+
+    Some(output)
+}
+
+#[get("/post/<id>?<highlight>&<debug>")]
+fn post(
+    posts: Snapshot,
+    config: &State<Config>,
+    cookies: &CookieJar<'_>,
+    id: PostId<'_>,
+    highlight: Option<&str>,
+    debug: Option<u8>,
+) -> Option<Html<String>> {
+    let post = posts.get(id.0)?;
+    let title = get_str(post, "title");
+    let re = highlight
+        .map(query::parse)
+        .map(|q| if config.loose_hyphens { q.loosen_hyphens() } else { q })
+        .and_then(|q| q.highlighter());
+    let body = render_post(post, re.as_ref(), debug.unwrap_or(0) != 0, config.max_body, config)?;
+    let canonical = format!("{}/post/{}", config.base_url, id.0);
+
+    Some(Html(format!(r#"<!DOCTYPE HTML>
+        <html{theme}>
             <head>
                 <title>Pheddit | {title}</title>
-                <link rel="stylesheet" type="text/css" href="/style.css">
+                <meta name="viewport" content="width=device-width, initial-scale=1">
+                <link rel="canonical" href="{canonical}">
+                <link rel="stylesheet" type="text/css" href="{style}">
+                <script src="{script}" defer></script>
+                {header}
             </head>
-            <body style="max-width: 800px; margin: auto;">
-                <h1>{title}</h1>
-    "#, title=title);
-    html::push_html(&mut output, Parser::new(text));
-    output += "
+            <body>
+                {toggle}
+                <button type="button" class="copy-link" data-url="{canonical}">Copy link</button>
+                {body}
+                {footer}
             </body>
         </html>
-    ";
-
-    Some(Html(output))
-}
-
-#[get("/candidates/<n>")]
-fn candidates(posts: &State<Posts>, n: usize) -> Html<String> {
-    let queries = vec![
-        "degree",
-        "career", "careers",
-        "programming",
-        "school",
-        "learn", "learning",
-        "switch", "switching",
-        "change", "changing",
-        "college", "university",
-        "advice",
-        "bootcamp", "bootcamps", "camp", "camps",
-        "self taught",
-    ];
-
-    let mut res = vec![];
-    for query in &queries {
-        let mut terms = vec![];
-        for word in query.split_whitespace() {
-            if let Ok(re) = Regex::new(&format!(r"(?i)\b{}\b", regex::escape(word))) {
-                terms.push(re);
-            }
-        }
-        res.push(terms);
-    }
-
-    let mut matches: Vec<_> = posts.map.par_iter()
-        .map(|(_id, post)| (post, get_str(post, "title"), get_str(post, "selftext")))
-        .filter(|(_post, title, text)| {
-            res.iter().any(|terms| {
-                terms.iter().all(|re| re.is_match(title) || re.is_match(text))
-            })
-        })
-        .map(|(post, _title, _text)| post)
+    "#, theme=theme(cookies), style=assets::url("style.css"), script=assets::url("copy.js"), toggle=THEME_TOGGLE, title=render::escape(title), canonical=render::escape_attr(&canonical), body=body, header=config.header, footer=config.footer)))
+}
+
+/// The post's stored JSON record, for debugging.
+#[get("/post/<id>/raw")]
+fn post_raw(posts: Snapshot, id: PostId<'_>) -> Option<Json<String>> {
+    let post = posts.get(id.0)?;
+    serde_json::to_string_pretty(&post.data).ok().map(Json)
+}
+
+/// Other posts with nearly the same body, like reposts and crossposts.
+#[get("/post/<id>/duplicates")]
+fn post_duplicates(posts: Snapshot, config: &State<Config>, id: PostId<'_>) -> Option<Json<String>> {
+    posts.get(id.0)?;
+    let duplicates: Vec<_> = dedup::duplicates(&posts, &config.text_fields(), id.0)
+        .into_iter()
+        .map(|(post, similarity)| json!({
+            "id": get_str(post, "id"),
+            "title": get_str(post, "title"),
+            "subreddit": get_str(post, "subreddit"),
+            "similarity": similarity,
+        }))
         .collect();
+    Some(Json(json!(duplicates).to_string()))
+}
 
-    matches.sort_by_key(|post| get_str(post, "id"));
+/// A post as plain text, for terminals and screen readers.  Post IDs can't
+/// contain dots, so this only gets requests that /post/<id> doesn't.
+#[get("/post/<file>", rank = 10)]
+fn post_text(posts: Snapshot, config: &State<Config>, file: &str) -> Option<(ContentType, String)> {
+    let id = PostId::from_param(file.strip_suffix(".txt")?).ok()?;
+    let post = posts.get(id.0)?;
+    let title = get_str(post, "title");
+    let text = render::plain_text(config.body(post));
+    Some((ContentType::Plain, format!("{}\n\n{}\n", title, text)))
+}
+
+/// Record a label for a candidate, like {"id": "abc", "label": "relevant"}.
+///
+/// Labels are appended to the labels file with a timestamp, so if a post is
+/// labeled more than once, the latest label wins.
+#[rocket::post("/candidates/label", data = "<body>")]
+fn label(posts: Snapshot, config: &State<Config>, body: String) -> Status {
+    let body: Value = match from_str(&body) {
+        Ok(body) => body,
+        Err(_) => return Status::BadRequest,
+    };
+    let (id, label) = match (body.get("id").and_then(Value::as_str), body.get("label").and_then(Value::as_str)) {
+        (Some(id), Some(label)) => (id, label),
+        _ => return Status::BadRequest,
+    };
+    if !posts.contains_key(id) {
+        return Status::NotFound;
+    }
 
-    let start = n * matches.len() / 3;
-    let end = (n + 1) * matches.len() / 3;
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let line = json!({"id": id, "label": label, "time": time}).to_string() + "\n";
+
+    // A single write of the whole line, so concurrent appends don't interleave
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&config.labels)
+        .and_then(|mut file| file.write_all(line.as_bytes()));
+    match result {
+        Ok(()) => Status::NoContent,
+        Err(e) => {
+            eprintln!("error: couldn't write {}: {}", config.labels.display(), e);
+            Status::InternalServerError
+        }
+    }
+}
+
+#[get("/recent?<limit>")]
+fn recent(posts: Snapshot, config: &State<Config>, cookies: &CookieJar<'_>, limit: Option<usize>) -> Html<String> {
+    let created = |post: &Post| post.get("created_utc").and_then(date::timestamp);
+
+    // Newest first, with undated posts last
+    let mut newest: Vec<_> = posts.par_iter().collect();
+    newest.sort_by_cached_key(|post| (Reverse(created(post)), get_str(post, "id")));
+    newest.truncate(limit.unwrap_or(50));
 
     let mut output = format!(r#"<!DOCTYPE HTML>
-        <html>
+        <html{theme}>
             <head>
-                <title>Pheddit Candidates | {n}/3</title>
-                <link rel="stylesheet" type="text/css" href="/style.css">
+                <title>Pheddit | Recent posts</title>
+                <meta name="viewport" content="width=device-width, initial-scale=1">
+                <link rel="stylesheet" type="text/css" href="{style}">
+                {header}
             </head>
-            <body style="max-width: 800px; margin: auto;">
-                <h2>Candidates {start}–{end} of {count}</em></h2>
+            <body>
+                {toggle}
+                <h2>Recent posts</h2>
                 <ul>
-    "#, n=n, start=start, end=end, count=matches.len());
+    "#, theme=theme(cookies), style=assets::url("style.css"), toggle=THEME_TOGGLE, header=config.header);
 
-    for post in &matches[start..end] {
-        let id = get_str(post, "id");
-        let title = get_str(post, "title");
-        output += &format!(r#"<li><a href="/post/{id}">{title}</a>"#, id=id, title=title);
+    for post in newest {
+        let when = created(post).map_or_else(String::new, |t| format!(r#" <span class="date">{}</span>"#, date::format_date(t)));
+        output += &format!(
+            r#"<li><a href="/post/{id}">{title}</a>{when}"#,
+            id=render::escape_attr(get_str(post, "id")),
+            title=render::escape(get_str(post, "title")),
+            when=when,
+        );
     }
 
     output += r#"
                 </ul>
+    "#;
+    output += &config.footer;
+    output += r#"
             </body>
         </html>
     "#;
@@ -199,28 +1026,1328 @@ fn candidates(posts: &State<Posts>, n: usize) -> Html<String> {
     Html(output)
 }
 
-#[launch]
-fn rocket() -> _ {
-    let paths: Vec<_> = env::args()
-        .skip(1)
-        .flat_map(|arg| fs::read_dir(&arg).unwrap())
-        .map(|file| file.unwrap().path())
-        .filter(|path| path.extension().map_or(false, |e| e == "json"))
+#[get("/stats/wordcloud?<subreddit>&<limit>")]
+fn wordcloud(posts: Snapshot, config: &State<Config>, subreddit: Option<&str>, limit: Option<usize>) -> Json<String> {
+    // Count how many posts each word appears in
+    let fields = config.text_fields();
+    let counts = posts.par_iter()
+        .filter(|post| subreddit.is_none_or(|s| in_subreddits(post, s)))
+        .map(|post| {
+            fields.iter()
+                .flat_map(|field| query::words(get_str(post, field)))
+                .filter(|word| !stop_words::is_stop_word(word))
+                .collect::<HashSet<_>>()
+        })
+        .fold(HashMap::new, |mut counts, words| {
+            for word in words {
+                *counts.entry(word).or_insert(0) += 1;
+            }
+            counts
+        })
+        .reduce(HashMap::new, |mut a, b| {
+            for (word, count) in b {
+                *a.entry(word).or_insert(0) += count;
+            }
+            a
+        });
+
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|(a, m), (b, n)| n.cmp(m).then(a.cmp(b)));
+    counts.truncate(limit.unwrap_or(100));
+
+    let words: Vec<_> = counts.into_iter()
+        .map(|(word, weight)| json!({"word": word, "weight": weight}))
+        .collect();
+    Json(Value::from(words).to_string())
+}
+
+#[get("/subreddits")]
+fn subreddits(posts: Snapshot) -> Json<String> {
+    let counts = posts.par_iter()
+        .map(|post| get_str(post, "subreddit"))
+        .filter(|subreddit| !subreddit.is_empty())
+        .fold(HashMap::new, |mut counts, subreddit| {
+            *counts.entry(subreddit).or_insert(0) += 1;
+            counts
+        })
+        .reduce(HashMap::new, |mut a, b| {
+            for (subreddit, count) in b {
+                *a.entry(subreddit).or_insert(0) += count;
+            }
+            a
+        });
+
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|(a, m), (b, n)| n.cmp(m).then(a.cmp(b)));
+
+    let subreddits: Vec<_> = counts.into_iter()
+        .map(|(subreddit, count)| json!({"subreddit": subreddit, "count": count}))
         .collect();
+    Json(Value::from(subreddits).to_string())
+}
+
+/// Whether the posts are being reloaded, and how far along that is.
+#[get("/status/reindex")]
+fn reindex_status(corpus: &State<Arc<Corpus>>) -> Json<String> {
+    let status = corpus.status();
+    Json(json!({
+        "state": if status.running() { "running" } else { "idle" },
+        "processed": status.processed(),
+        "last_reload": status.last_reload(),
+    }).to_string())
+}
+
+#[get("/api/meta")]
+fn api_meta(posts: Snapshot, config: &State<Config>) -> Json<String> {
+    let meta = posts.meta(&config.text_fields());
+    Json(json!({
+        "posts": meta.posts,
+        "subreddits": meta.subreddits,
+        "authors": meta.authors,
+        "earliest": meta.earliest,
+        "latest": meta.latest,
+        "tokens": meta.tokens,
+    }).to_string())
+}
+
+/// The summary of a post in API search results.
+fn api_item(post: &Post) -> Value {
+    json!({
+        "id": get_str(post, "id"),
+        "title": get_str(post, "title"),
+        "subreddit": get_str(post, "subreddit"),
+        "created_utc": post.get("created_utc"),
+    })
+}
 
-    let posts = Posts {
-        map: paths.par_iter()
-            .map(|path| File::open(path).unwrap())
-            .map(BufReader::new)
-            .flat_map_iter(|reader| reader.lines())
-            .map(Result::unwrap)
-            .map(|line| from_str::<Value>(&line).unwrap())
-            .map(|post| (get_str(&post, "id").to_string(), post))
-            .collect(),
+#[get("/api/search?<highlight>&<params..>")]
+fn api_search(posts: Snapshot, config: &State<Config>, history: &State<History>, highlight: Option<u8>, params: SearchParams<'_>) -> Json<String> {
+    history.record(params.query);
+    let mut results = run_search(&posts, config, &params);
+    let subreddits: Vec<_> = results.subreddit_counts()
+        .into_iter()
+        .map(|(subreddit, count)| json!({"subreddit": subreddit, "count": count}))
+        .collect();
+
+    // Only page through the most relevant matches of huge result sets
+    let total = results.matches.len();
+    let truncated = total > config.max_api_results;
+    results.keep_most_relevant(config.max_api_results, &search_fields(config, &params));
+
+    let (page, pages, range) = params.paginate(config, results.matches.len());
+    let re = results.query.highlighter();
+
+    let items: Vec<_> = results.matches[range].iter()
+        .map(|post| {
+            let mut item = api_item(post);
+            if highlight.unwrap_or(0) != 0 {
+                item["title_html"] = render::highlight(get_str(post, "title"), re.as_ref()).into();
+                item["snippet_html"] = params.snippet(config.body(post), re.as_ref()).into();
+            }
+            item
+        })
+        .collect();
+
+    Json(json!({
+        "query": params.query,
+        "count": results.matches.len(),
+        "total": total,
+        "truncated": truncated,
+        "any_terms": results.any_terms,
+        "subreddits": subreddits,
+        "page": page,
+        "pages": pages,
+        "results": items,
+    }).to_string())
+}
+
+/// A position in the results of a search, as of one snapshot of the posts.
+struct Cursor {
+    generation: u64,
+    offset: usize,
+}
+
+impl Cursor {
+    /// Parse a cursor token like `2a.150`.
+    fn parse(token: &str) -> Option<Self> {
+        let (generation, offset) = token.split_once('.')?;
+        Some(Self {
+            generation: u64::from_str_radix(generation, 16).ok()?,
+            offset: offset.parse().ok()?,
+        })
+    }
+
+    fn token(&self) -> String {
+        format!("{:x}.{}", self.generation, self.offset)
+    }
+}
+
+/// Scroll through search results a page at a time.  Each page includes an
+/// opaque `next_cursor` to pass back for the next one, or null at the end.
+/// Cursors are tied to the snapshot of the posts they came from, so if the
+/// posts are reloaded in between, the request fails with 409 Conflict rather
+/// than skipping or repeating results; start again without a cursor.
+#[get("/api/search/scroll?<cursor>&<params..>")]
+fn api_search_scroll(posts: Snapshot, config: &State<Config>, cursor: Option<&str>, params: SearchParams<'_>) -> Result<Json<String>, Status> {
+    let offset = match cursor {
+        Some(token) => {
+            let cursor = Cursor::parse(token).ok_or(Status::BadRequest)?;
+            if cursor.generation != posts.generation() {
+                return Err(Status::Conflict);
+            }
+            cursor.offset
+        }
+        None => 0,
+    };
+
+    let mut results = run_search(&posts, config, &params);
+    results.keep_most_relevant(config.max_api_results, &search_fields(config, &params));
+    let count = results.matches.len();
+
+    let per_page = params.per_page.unwrap_or(config.per_page).clamp(1, MAX_PER_PAGE);
+    let start = offset.min(count);
+    let end = (start + per_page).min(count);
+    let items: Vec<_> = results.matches[start..end].iter()
+        .map(|post| api_item(post))
+        .collect();
+
+    let next = (end < count).then(|| Cursor { generation: posts.generation(), offset: end }.token());
+    Ok(Json(json!({
+        "query": params.query,
+        "count": count,
+        "results": items,
+        "next_cursor": next,
+    }).to_string()))
+}
+
+/// Count the posts matching a search per day, week, or month they were
+/// posted, like [{"date": "2021-09-27", "count": 3}, ...].  Each bucket is
+/// named by its first day, and undated posts aren't counted.
+#[get("/api/timeseries?<bucket>&<params..>")]
+fn api_timeseries(posts: Snapshot, config: &State<Config>, bucket: Option<&str>, params: SearchParams<'_>) -> Result<Json<String>, Status> {
+    let unit = bucket.unwrap_or("day");
+    if date::truncate(0, unit).is_none() {
+        return Err(Status::BadRequest);
+    }
+
+    let mut counts = HashMap::new();
+    for post in run_search(&posts, config, &params).matches {
+        if let Some(start) = post.get("created_utc").and_then(date::timestamp).and_then(|t| date::truncate(t, unit)) {
+            *counts.entry(start).or_insert(0) += 1;
+        }
+    }
+
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort();
+    let buckets: Vec<_> = counts.into_iter()
+        .map(|(start, count)| json!({"date": date::format_date(start), "count": count}))
+        .collect();
+    Ok(Json(json!(buckets).to_string()))
+}
+
+/// The most past queries /suggest/queries returns.
+const MAX_SUGGESTIONS: usize = 10;
+
+/// Suggest past search queries that start with a prefix, most frequent first.
+#[get("/suggest/queries?<prefix>")]
+fn suggest_queries(history: &State<History>, prefix: Option<&str>) -> Json<String> {
+    let suggestions: Vec<_> = history.suggest(prefix.unwrap_or(""), MAX_SUGGESTIONS)
+        .into_iter()
+        .map(|(query, count)| json!({"query": query, "count": count}))
+        .collect();
+    Json(json!(suggestions).to_string())
+}
+
+/// Export every match of a search as NDJSON, one post per line.
+#[get("/search.jsonl?<params..>")]
+fn search_jsonl(posts: Snapshot, config: &State<Config>, params: SearchParams<'_>) -> (ContentType, TextStream![String]) {
+    let ids: Vec<_> = run_search(&posts, config, &params)
+        .matches
+        .into_iter()
+        .map(|post| get_str(post, "id").to_string())
+        .collect();
+
+    // Serialize the posts as they're sent, rather than all up front
+    let stream = TextStream! {
+        for id in ids {
+            if let Some(post) = posts.get(&id) {
+                yield post.data.to_string() + "\n";
+            }
+        }
     };
-    eprintln!("Loaded {} posts...", posts.map.len());
+    (ContentType::new("application", "x-ndjson"), stream)
+}
+
+/// API clients expect JSON, even for errors.
+#[catch(404)]
+fn api_not_found(req: &Request<'_>) -> Json<String> {
+    Json(json!({"error": "not found", "path": req.uri().path().as_str()}).to_string())
+}
+
+/// The most posts that can be fetched by one /api/posts request.
+const MAX_BATCH: usize = 100;
+
+#[rocket::post("/api/posts", data = "<ids>")]
+fn api_posts(posts: Snapshot, ids: String) -> Result<Json<String>, Status> {
+    let ids: Vec<String> = from_str(&ids).map_err(|_| Status::BadRequest)?;
+    if ids.len() > MAX_BATCH {
+        return Err(Status::BadRequest);
+    }
+
+    let mut found = serde_json::Map::new();
+    let mut missing = vec![];
+    for id in ids {
+        match posts.get(&id) {
+            Some(post) => {
+                found.insert(id, post.data.clone());
+            }
+            None => missing.push(id),
+        }
+    }
+
+    Ok(Json(json!({"posts": found, "missing": missing}).to_string()))
+}
+
+/// Compare two secrets in time that depends only on their lengths, so the
+/// comparison doesn't reveal how much of a guess was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// A request guard for the /admin endpoints, which checks the X-Admin-Token
+/// header against PHEDDIT_ADMIN_TOKEN.
+struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, ()> {
+        let config = req.rocket().state::<Config>().unwrap();
+        let token = match &config.admin_token {
+            Some(token) => token,
+            None => return Outcome::Failure((Status::NotFound, ())),
+        };
+
+        match req.headers().get_one("X-Admin-Token") {
+            Some(given) if constant_time_eq(given.as_bytes(), token.as_bytes()) => Outcome::Success(Admin),
+            _ => Outcome::Failure((Status::Unauthorized, ())),
+        }
+    }
+}
+
+/// The largest NDJSON body /admin/ingest accepts.
+const MAX_INGEST: ByteUnit = ByteUnit::Gibibyte(1);
+
+/// Add posts to the live index without a full reload, from either a file in
+/// one of the input directories (?path=) or an uploaded NDJSON body.
+#[rocket::post("/admin/ingest?<path>", data = "<body>")]
+async fn admin_ingest(_admin: Admin, corpus: &State<Arc<Corpus>>, path: Option<String>, body: Data<'_>) -> Result<Json<String>, Status> {
+    let (path, text) = match path {
+        Some(path) => {
+            // Don't let the endpoint read arbitrary files on the server
+            let path = match corpus.input_file(Path::new(&path)) {
+                Some(path) => path,
+                None => return Err(Status::Forbidden),
+            };
+            let bytes = rocket::tokio::fs::read(&path).await.map_err(|e| {
+                eprintln!("error: couldn't ingest {}: {}", path.display(), e);
+                Status::BadRequest
+            })?;
+            let text = String::from_utf8_lossy(&bytes).into_owned();
+            (path, text)
+        }
+        None => {
+            let text = body.open(MAX_INGEST).into_string().await.map_err(|_| Status::BadRequest)?;
+            if !text.is_complete() {
+                return Err(Status::PayloadTooLarge);
+            }
+            (PathBuf::from("ingest"), text.into_inner())
+        }
+    };
+
+    let corpus = corpus.inner().clone();
+    let (ingested, total) = rocket::tokio::task::spawn_blocking(move || (corpus.ingest(&path, &text), corpus.posts().len()))
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+    Ok(Json(json!({"ingested": ingested, "posts": total}).to_string()))
+}
+
+#[get("/api/posts/<id>/selftext")]
+fn api_selftext(posts: Snapshot, config: &State<Config>, id: PostId<'_>) -> Option<String> {
+    let post = posts.get(id.0)?;
+    Some(config.body(post).to_string())
+}
+
+#[launch]
+fn rocket() -> _ {
+    let mut dirs = vec![];
+    let mut reindex_interval = None;
+    let mut export_dir = None;
+    let mut dedup_report = false;
+    let mut tls_cert = None;
+    let mut tls_key = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--reindex-interval" => {
+                let secs = args.next()
+                    .and_then(|s| s.parse().ok())
+                    .expect("--reindex-interval needs a number of seconds");
+                reindex_interval = Some(Duration::from_secs(secs));
+            }
+            "--export" => {
+                let dir = args.next().expect("--export needs a directory");
+                export_dir = Some(PathBuf::from(dir));
+            }
+            "--dedup-report" => dedup_report = true,
+            "--tls-cert" => {
+                let path = args.next().expect("--tls-cert needs a path");
+                tls_cert = Some(PathBuf::from(path));
+            }
+            "--tls-key" => {
+                let path = args.next().expect("--tls-key needs a path");
+                tls_key = Some(PathBuf::from(path));
+            }
+            _ => dirs.push(PathBuf::from(arg)),
+        }
+    }
+
+    let mut config = Config::from_env();
+    config.tls_cert = tls_cert.or(config.tls_cert);
+    config.tls_key = tls_key.or(config.tls_key);
+    let figment = config.figment().unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    });
+
+    let corpus = Arc::new(Corpus::new(dirs));
+
+    // Write a static copy of the archive instead of serving it
+    if let Some(dir) = export_dir {
+        if let Err(e) = export::export(&corpus.posts(), &dir, &config) {
+            eprintln!("error: couldn't export to {}: {}", dir.display(), e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    if dedup_report {
+        dedup::report(&corpus.posts(), &config.text_fields());
+    }
+
+    if let Some(interval) = reindex_interval {
+        let corpus = corpus.clone();
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            if let Err(e) = corpus.reload() {
+                eprintln!("error: couldn't reload posts: {}", e);
+            }
+        });
+    }
+
+    build(figment, corpus, config)
+}
+
+/// Set up the server for some posts.
+fn build(figment: Figment, corpus: Arc<Corpus>, config: Config) -> Rocket<Build> {
+    rocket::custom(figment)
+        .manage(corpus)
+        .manage(config)
+        .manage(History::default())
+        .mount("/", routes![index, assets::asset, set_theme, search, search_jsonl, post, post_raw, post_duplicates, post_text, recent, label, wordcloud, subreddits, api_search, api_search_scroll, api_timeseries, suggest_queries, api_meta, reindex_status, api_posts, api_selftext, admin_ingest])
+        .mount("/", candidates::routes())
+        .register("/api", catchers![api_not_found])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    use rocket::http::Header;
+
+    #[test]
+    fn dark_theme() {
+        let client = client(&[]);
+        let css = get(&client, "/assets/style.css");
+        assert!(css.contains("@media (prefers-color-scheme: dark)"));
+
+        let page = client.get("/").cookie(Cookie::new("theme", "dark")).dispatch().into_string().unwrap();
+        assert!(page.contains(r#"<html data-theme="dark">"#));
+        let page = get(&client, "/");
+        assert!(page.contains("<html>"));
+    }
+
+    #[test]
+    fn theme_redirect() {
+        let client = client(&[]);
+        let location = |referer: &str| {
+            let response = client.get("/theme/dark")
+                .header(Header::new("Host", "pheddit.example"))
+                .header(Header::new("Referer", referer.to_string()))
+                .dispatch();
+            assert_eq!(response.status(), Status::SeeOther);
+            response.headers().get_one("Location").unwrap().to_string()
+        };
+
+        assert_eq!(location("https://pheddit.example/search?query=rust"), "/search?query=rust");
+        assert_eq!(location("http://pheddit.example"), "/");
+        assert_eq!(location("https://evil.example/"), "/");
+        assert_eq!(location("https://pheddit.example//evil.example/"), "/");
+        assert_eq!(location("/search"), "/");
+    }
+
+    #[test]
+    fn viewport() {
+        let client = client(&[]);
+        let page = get(&client, "/");
+        assert!(page.contains(r#"<meta name="viewport" content="width=device-width, initial-scale=1">"#));
+    }
+
+    #[test]
+    fn no_results() {
+        let client = client(&[fixture("a", "Rust jobs", "Learning Rust")]);
+        let page = get(&client, "/search?query=haskell");
+        assert!(page.contains("0 results"));
+        assert!(page.contains("No posts matched your search."));
+        assert!(!page.contains("data-post"));
+    }
+
+    #[test]
+    fn sort_by_title() {
+        let client = client(&[
+            fixture("a", "banana rust", ""),
+            fixture("b", "Apple rust", ""),
+            fixture("c", "cherry rust", ""),
+            fixture("d", "apple rust", ""),
+        ]);
+        assert_eq!(search_ids(&client, "query=rust&sort=title"), ["b", "d", "a", "c"]);
+        assert_eq!(search_ids(&client, "query=rust&sort=title:desc"), ["c", "a", "d", "b"]);
+    }
+
+    #[test]
+    fn min_match() {
+        let client = client(&[
+            fixture("a", "rust go java", ""),
+            fixture("b", "rust go", ""),
+            fixture("c", "rust", ""),
+            fixture("d", "rust go python", ""),
+        ]);
+        assert_eq!(search_ids(&client, "query=rust+go+java&min_match=2&sort=id"), ["a", "b", "d"]);
+        assert_eq!(search_ids(&client, "query=rust+go+-python&min_match=1&sort=id"), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn malformed_ids() {
+        assert!(PostId::from_param("abc_123").is_ok());
+        for id in ["", "..", "..%2f", "../etc", "ABC", "a b", "a.txt"] {
+            assert!(PostId::from_param(id).is_err(), "{}", id);
+        }
+
+        let client = client(&[fixture("abc", "Title", "Body")]);
+        assert_eq!(client.get("/post/abc").dispatch().status(), Status::Ok);
+        for uri in ["/post/..%2f", "/post/ABC", "/post/abc%20", "/post/..%2f/raw", "/api/posts/..%2f/selftext"] {
+            assert_eq!(client.get(uri).dispatch().status(), Status::NotFound, "{}", uri);
+        }
+    }
+
+    #[test]
+    fn wordcloud_weights() {
+        let client = client(&[
+            fixture("a", "Rust jobs", "Rust is the best"),
+            fixture("b", "Rust careers", "Learning the language"),
+            fixture("c", "Go jobs", "Not rust"),
+        ]);
+        let words = get_json(&client, "/stats/wordcloud?limit=2");
+        assert_eq!(words, json!([{"word": "rust", "weight": 3}, {"word": "jobs", "weight": 2}]));
+    }
+
+    #[test]
+    fn batch_posts() {
+        let client = client(&[fixture("a", "A", ""), fixture("b", "B", "")]);
+        let response = client.post("/api/posts").body(r#"["a", "missing", "b"]"#).dispatch();
+        let json: Value = from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(json["posts"]["a"]["title"], "A");
+        assert_eq!(json["posts"]["b"]["title"], "B");
+        assert_eq!(json["posts"].as_object().unwrap().len(), 2);
+        assert_eq!(json["missing"], json!(["missing"]));
+
+        let response = client.post("/api/posts").body("not json").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let ids = vec!["a"; MAX_BATCH + 1];
+        let response = client.post("/api/posts").body(json!(ids).to_string()).dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn link_post_attributes() {
+        let url = r#"https://example.com/?a=1&b="><script>"#;
+        let client = client(&[json!({"id": "a", "title": "Link", "selftext": "", "is_self": false, "url": url})]);
+        let page = get(&client, "/post/a");
+        assert!(page.contains(r#"<a href="https://example.com/?a=1&amp;b=&quot;&gt;&lt;script&gt;">"#));
+        assert!(!page.contains("<script>"));
+    }
+
+    #[test]
+    fn stop_word_languages() {
+        let client = client(&[fixture("a", "Rust para todos", ""), fixture("b", "Rust jobs", "")]);
+        assert_eq!(search_ids(&client, "query=rust+para&lang=es&sort=id"), ["a", "b"]);
+        assert_eq!(search_ids(&client, "query=rust+para&lang=en&sort=id"), ["a"]);
+        assert_eq!(search_ids(&client, "query=rust+the&sort=id"), ["a", "b"]);
+        assert_eq!(search_ids(&client, "query=rust+-the&sort=id"), ["a", "b"]);
+    }
+
+    #[test]
+    fn short_terms() {
+        let client = client(&[fixture("a", "Rust and C", ""), fixture("b", "Rust jobs", ""), fixture("c", "C jobs", "")]);
+        assert_eq!(search_ids(&client, "query=rust+c&sort=id"), ["a", "b"]);
+        assert_eq!(search_ids(&client, "query=c&sort=id"), ["a", "c"]);
+        // Negating a dropped word drops the negation, rather than excluding everything
+        assert_eq!(search_ids(&client, "query=rust+-a&sort=id"), ["a", "b"]);
+    }
+
+    #[test]
+    fn pager_links() {
+        let params = SearchParams { query: "rust", ..Default::default() };
+        let pager = pager(&params, 3, 48);
+        assert!(pager.contains("Page 3 of 48"));
+        for page in [1, 2, 4, 5, 48] {
+            assert!(pager.contains(&format!(r#"<a href="/search?query=rust&amp;page={0}">{0}</a>"#, page)), "{}", page);
+        }
+        assert!(pager.contains("<strong>3</strong>"));
+        assert!(!pager.contains("page=6"));
+        assert!(pager.contains("… <a href=\"/search?query=rust&amp;page=48\">"));
+
+        let client = client(&(0..5).map(|i| fixture(&i.to_string(), "rust", "")).collect::<Vec<_>>());
+        let page = get(&client, "/search?query=rust&per_page=2&page=2");
+        assert!(page.contains("Page 2 of 3"));
+        assert_eq!(page.matches("<li data-post").count(), 2);
+    }
+
+    #[test]
+    fn subreddit_link() {
+        let client = client(&[json!({"id": "a", "title": "Post", "selftext": "", "subreddit": "learnrust"})]);
+        let page = get(&client, "/post/a");
+        assert!(page.contains(r#"<a href="https://www.reddit.com/r/learnrust">r/learnrust</a>"#));
+    }
+
+    #[test]
+    fn labels() {
+        let dir = TempDir::new();
+        let mut config = config();
+        config.labels = dir.path().join("labels.jsonl");
+        let client = client_with(&[fixture("a", "Post", "")], config);
+
+        let response = client.post("/candidates/label").body(r#"{"id": "a", "label": "relevant"}"#).dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+        let response = client.post("/candidates/label").body(r#"{"id": "zz", "label": "relevant"}"#).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let response = client.post("/candidates/label").body(r#"{"id": "a"}"#).dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+
+        let text = std::fs::read_to_string(dir.path().join("labels.jsonl")).unwrap();
+        let lines: Vec<Value> = text.lines().map(|line| from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["id"], "a");
+        assert_eq!(lines[0]["label"], "relevant");
+        assert!(lines[0]["time"].is_u64());
+    }
+
+    #[test]
+    fn title_scope() {
+        let client = client(&[fixture("a", "Rust jobs", ""), fixture("b", "Careers", "Learning rust")]);
+        assert_eq!(search_ids(&client, "query=rust&sort=id"), ["a", "b"]);
+        assert_eq!(search_ids(&client, "query=rust&scope=title&sort=id"), ["a"]);
+    }
 
-    rocket::build()
-        .manage(posts)
-        .mount("/", routes![index, style, search, post, candidates])
+    #[test]
+    fn removed_notice() {
+        let client = client(&[fixture("a", "Gone", "[removed]"), fixture("b", "Also gone", "[deleted]")]);
+        let page = get(&client, "/post/a");
+        assert!(page.contains("Gone"));
+        assert!(page.contains(r#"<p class="removed">This post was removed.</p>"#));
+        assert!(!page.contains("[removed]"));
+        let page = get(&client, "/post/b");
+        assert!(page.contains("This post was deleted by its author."));
+    }
+
+    #[test]
+    fn source_filter() {
+        let dir = TempDir::new();
+        for source in ["reddit", "pushshift"] {
+            std::fs::create_dir(dir.path().join(source)).unwrap();
+        }
+        dir.write_posts("reddit/posts.json", &[fixture("a", "Rust jobs", "")]);
+        dir.write_posts("pushshift/posts.json", &[fixture("b", "Rust careers", "")]);
+        let corpus = Corpus::new(vec![dir.path().join("reddit"), dir.path().join("pushshift")]);
+        let client = serve(Arc::new(corpus), config());
+
+        assert_eq!(search_ids(&client, "query=rust&sort=id"), ["a", "b"]);
+        assert_eq!(search_ids(&client, "query=rust&source=reddit"), ["a"]);
+        assert_eq!(search_ids(&client, "query=rust&source=pushshift"), ["b"]);
+        let page = get(&client, "/post/b");
+        assert!(page.contains(&format!("Source: {}", dir.path().join("pushshift").display())));
+    }
+
+    #[test]
+    fn debug_file() {
+        let dir = TempDir::new();
+        dir.write_posts("2021<a>.json", &[fixture("a", "Post", "")]);
+        let client = serve(Arc::new(Corpus::new(vec![dir.path().to_path_buf()])), config());
+
+        assert!(!get(&client, "/post/a").contains("File:"));
+        let page = get(&client, "/post/a?debug=1");
+        assert!(page.contains("File: "));
+        assert!(page.contains("2021&lt;a&gt;.json"));
+        assert!(!page.contains("2021<a>.json"));
+    }
+
+    #[test]
+    fn deterministic_order() {
+        let posts: Vec<_> = (0..100).rev().map(|i| fixture(&format!("p{:03}", i), "Rust jobs", "")).collect();
+        let client = client(&posts);
+        let first = search_ids(&client, "query=rust&per_page=100");
+        assert_eq!(first.len(), 100);
+        assert!(first.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(search_ids(&client, "query=rust&per_page=100"), first);
+    }
+
+    #[test]
+    fn relevance_by_default() {
+        let client = client(&[
+            fixture("a", "Careers", "I like rust"),
+            fixture("b", "Rust jobs", "Who's hiring for rust?"),
+            fixture("c", "Jobs", "Rust, rust, rust"),
+        ]);
+        assert_eq!(search_ids(&client, "query=rust"), ["b", "c", "a"]);
+        assert_eq!(search_ids(&client, "query=rust&sort=id"), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn all_stop_words() {
+        let client = client(&[
+            fixture("a", "How to be a programmer", ""),
+            fixture("b", "Rust jobs", ""),
+            fixture("c", "Learning to code", ""),
+        ]);
+        assert_eq!(search_ids(&client, "query=how+to+be&sort=id"), ["a"]);
+        let page = get(&client, "/search?query=how+to+be");
+        assert!(page.contains("Your query only contains very common words"));
+    }
+
+    #[test]
+    fn subreddit_counts() {
+        let post = |id: &str, subreddit: &str| json!({"id": id, "title": "Post", "selftext": "", "subreddit": subreddit});
+        let client = client(&[post("a", "learnrust"), post("b", "rust"), post("c", "rust"), fixture("d", "No subreddit", "")]);
+        assert_eq!(get_json(&client, "/subreddits"), json!([
+            {"subreddit": "rust", "count": 2},
+            {"subreddit": "learnrust", "count": 1},
+        ]));
+    }
+
+    #[test]
+    fn edited_indicator() {
+        let post = |id: &str, edited: Value| json!({"id": id, "title": "Post", "selftext": "", "created_utc": 1600000000, "edited": edited});
+        let client = client(&[post("a", json!(1600003600)), post("b", json!(false))]);
+        let page = get(&client, "/post/a");
+        assert!(page.contains("(edited)</span>"));
+        assert!(page.contains(&format!(r#"<span title="{}">"#, date::format_datetime(1600003600))));
+        assert!(!get(&client, "/post/b").contains("(edited)"));
+    }
+
+    #[test]
+    fn flair() {
+        let post = |id: &str, flair: &str| json!({"id": id, "title": "Rust jobs", "selftext": "", "link_flair_text": flair});
+        let client = client(&[post("a", "Student <3"), post("b", "Meta"), post("c", "")]);
+        let badge = r#"<span class="flair">Student &lt;3</span>"#;
+        assert!(get(&client, "/search?query=rust").contains(badge));
+        assert!(get(&client, "/post/a").contains(badge));
+        assert!(!get(&client, "/post/c").contains(r#"class="flair""#));
+
+        assert_eq!(search_ids(&client, "query=rust&flair=meta"), ["b"]);
+    }
+
+    #[test]
+    fn corpus_meta() {
+        let post = |id: &str, subreddit: &str, author: &str, created: i64| {
+            json!({"id": id, "title": "Rust jobs", "selftext": "Hiring now", "subreddit": subreddit, "author": author, "created_utc": created})
+        };
+        let client = client(&[post("a", "rust", "alice", 300), post("b", "rust", "bob", 100), post("c", "learnrust", "alice", 200)]);
+        let meta = get_json(&client, "/api/meta");
+        assert_eq!(meta["posts"], 3);
+        assert_eq!(meta["subreddits"], 2);
+        assert_eq!(meta["authors"], 2);
+        assert_eq!(meta["earliest"], 100);
+        assert_eq!(meta["latest"], 300);
+        assert_eq!(meta["tokens"], 12);
+    }
+
+    #[test]
+    fn related_terms() {
+        let client = client(&[
+            fixture("a", "Rust jobs", "Hiring remote engineers"),
+            fixture("b", "Rust careers", "Remote work is common"),
+            fixture("c", "Python jobs", "Django developers wanted"),
+        ]);
+        let page = get(&client, "/search?query=rust");
+        let related = &page[page.find(r#"<p class="related">"#).unwrap()..];
+        let related = &related[..related.find("</p>").unwrap()];
+        assert!(related.starts_with(r#"<p class="related">Related terms: <a href="/search?query=rust%20remote&amp;page=1">remote</a>"#), "{}", related);
+        assert!(related.contains(">jobs</a>"));
+        assert!(!related.contains(">rust</a>"));
+        assert!(!related.contains("django"));
+        assert!(!related.contains(">is</a>"));
+    }
+
+    #[test]
+    fn related_terms_keep_filters() {
+        let client = client(&[
+            json!({"id": "a", "title": "Rust jobs", "selftext": "Hiring remote engineers", "subreddit": "rust"}),
+            json!({"id": "b", "title": "Rust careers", "selftext": "Remote work is common", "subreddit": "rust"}),
+        ]);
+        let page = get(&client, "/search?query=rust&subreddit=rust&lang=es&sort=date");
+        let related = &page[page.find(r#"<p class="related">"#).unwrap()..];
+        let related = &related[..related.find("</p>").unwrap()];
+        assert!(related.contains(r#"<a href="/search?query=rust%20remote&amp;sort=date&amp;lang=es&amp;subreddit=rust&amp;page=1">remote</a>"#), "{}", related);
+    }
+
+    #[test]
+    fn page_size() {
+        let posts: Vec<_> = (0..600).map(|i| fixture(&format!("p{}", i), "Rust jobs", "")).collect();
+        let mut config = config();
+        config.per_page = 7;
+        let client = client_with(&posts, config);
+
+        assert_eq!(search_ids(&client, "query=rust").len(), 7);
+        assert_eq!(get(&client, "/search?query=rust").matches("<li data-post").count(), 7);
+        assert_eq!(search_ids(&client, "query=rust&per_page=10000").len(), MAX_PER_PAGE);
+        assert_eq!(get(&client, "/search?query=rust&per_page=10000").matches("<li data-post").count(), MAX_PER_PAGE);
+    }
+
+    #[test]
+    fn canonical_url() {
+        let mut config = config();
+        config.base_url = "https://pheddit.example".to_string();
+        let client = client_with(&[fixture("abc", "Post", "")], config);
+        let page = get(&client, "/post/abc");
+        assert!(page.contains(r#"<link rel="canonical" href="https://pheddit.example/post/abc">"#));
+        assert!(page.contains(r#"data-url="https://pheddit.example/post/abc">Copy link</button>"#));
+    }
+
+    #[test]
+    fn subreddit_list() {
+        let post = |id: &str, subreddit: &str| json!({"id": id, "title": "Rust jobs", "selftext": "", "subreddit": subreddit});
+        let client = client(&[post("a", "cscareerquestions"), post("b", "learnprogramming"), post("c", "rust")]);
+        assert_eq!(search_ids(&client, "query=rust&subreddit=CSCareerQuestions,learnprogramming&sort=id"), ["a", "b"]);
+        assert_eq!(search_ids(&client, "query=rust&subreddit=rust"), ["c"]);
+    }
+
+    #[test]
+    fn empty_corpus() {
+        let page = get(&client(&[]), "/");
+        assert!(page.contains("No posts are loaded — check your input paths."));
+        let page = get(&client(&[fixture("a", "Post", "")]), "/");
+        assert!(!page.contains("No posts are loaded"));
+    }
+
+    #[test]
+    fn api_highlight() {
+        let client = client(&[fixture("a", "Rust <jobs>", "Where can I find a rust job?")]);
+        let json = get_json(&client, "/api/search?query=rust&highlight=1");
+        let item = &json["results"][0];
+        assert_eq!(item["title_html"], "<mark>Rust</mark> &lt;jobs&gt;");
+        assert!(item["snippet_html"].as_str().unwrap().contains("<mark>rust</mark>"));
+
+        let json = get_json(&client, "/api/search?query=rust");
+        assert!(json["results"][0].get("snippet_html").is_none());
+    }
+
+    #[test]
+    fn jsonl_export() {
+        let mut posts: Vec<_> = (0..120).map(|i| fixture(&format!("p{}", i), "Rust jobs", "")).collect();
+        posts.push(fixture("x", "Go jobs", ""));
+        let client = client(&posts);
+
+        let response = client.get("/search.jsonl?query=rust&per_page=10").dispatch();
+        assert_eq!(response.content_type(), Some(ContentType::new("application", "x-ndjson")));
+        let lines: Vec<Value> = response.into_string()
+            .unwrap()
+            .lines()
+            .map(|line| from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 120);
+        assert!(lines.iter().all(|post| post["title"] == "Rust jobs"));
+    }
+
+    #[test]
+    fn api_not_found() {
+        let client = client(&[]);
+        let response = client.get("/api/xyz").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        let json: Value = from_str(&response.into_string().unwrap()).unwrap();
+        assert_eq!(json, json!({"error": "not found", "path": "/api/xyz"}));
+
+        let response = client.get("/xyz").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+    }
+
+    #[test]
+    fn archived_date() {
+        let client = client(&[
+            json!({"id": "a", "title": "Post", "selftext": "", "retrieved_utc": 1600000000}),
+            json!({"id": "b", "title": "Post", "selftext": "", "retrieved_on": 1500000000}),
+            fixture("c", "Post", ""),
+        ]);
+        let archived = format!("Archived: {}", date::format_datetime(1600000000));
+        assert!(!get(&client, "/post/a").contains("Archived:"));
+        assert!(get(&client, "/post/a?debug=1").contains(&archived));
+        assert!(get(&client, "/post/b?debug=1").contains(&format!("Archived: {}", date::format_datetime(1500000000))));
+        assert!(!get(&client, "/post/c?debug=1").contains("Archived:"));
+    }
+
+    #[test]
+    fn long_post() {
+        // "é" is two bytes, so a limit of 11 bytes falls inside one
+        let mut config = config();
+        config.max_body = 11;
+        let client = client_with(&[fixture("a", "Long", "ééééééééééééééé"), fixture("b", "Short", "éé")], config);
+
+        let page = get(&client, "/post/a");
+        assert!(page.contains("<p>ééééé</p>"), "{}", page);
+        assert!(page.contains("This post is too long to show in full."));
+        assert!(page.contains(r#"<a href="/api/posts/a/selftext">Download the full text</a>"#));
+        assert!(!get(&client, "/post/b").contains("too long"));
+    }
+
+    #[test]
+    fn recent_posts() {
+        let post = |id: &str, created: i64| json!({"id": id, "title": id, "selftext": "", "created_utc": created});
+        let client = client(&[post("a", 100), post("b", 300), fixture("c", "c", ""), post("d", 200)]);
+        let page = get(&client, "/recent");
+        let order: Vec<_> = ["a", "b", "c", "d"].iter().map(|id| page.find(&format!(r#"<a href="/post/{}">"#, id)).unwrap()).collect();
+        assert!(order[1] < order[3] && order[3] < order[0] && order[0] < order[2]);
+
+        let page = get(&client, "/recent?limit=1");
+        assert_eq!(page.matches("<li>").count(), 1);
+        assert!(page.contains(r#"<a href="/post/b">"#));
+    }
+
+    #[test]
+    fn header_footer() {
+        let mut config = config();
+        config.header = r#"<link rel="stylesheet" href="/custom.css">"#.to_string();
+        config.footer = r#"<footer class="banner">Hosted by the archive team</footer>"#.to_string();
+        let client = client_with(&[fixture("a", "Post", "")], config);
+
+        for uri in ["/", "/post/a"] {
+            let page = get(&client, uri);
+            let head = &page[..page.find("</head>").unwrap()];
+            assert!(head.contains(r#"<link rel="stylesheet" href="/custom.css">"#), "{}", uri);
+            let body = &page[page.find("<body>").unwrap()..];
+            assert!(body.contains(r#"<footer class="banner">Hosted by the archive team</footer>"#), "{}", uri);
+        }
+    }
+
+    #[test]
+    fn fallback_any() {
+        let client = client(&[
+            fixture("a", "Rust jobs", ""),
+            fixture("b", "Go careers", ""),
+            fixture("c", "Python", ""),
+        ]);
+        assert_eq!(search_ids(&client, "query=rust+go"), Vec::<String>::new());
+        assert_eq!(search_ids(&client, "query=rust+go&fallback=any&sort=id"), ["a", "b"]);
+        let page = get(&client, "/search?query=rust+go&fallback=any");
+        assert!(page.contains("No posts matched all terms; showing posts matching any term."));
+
+        // Filters aren't relaxed
+        assert_eq!(search_ids(&client, "query=rust+go+-jobs&fallback=any"), ["b"]);
+        assert_eq!(search_ids(&client, "query=zzzz+-rust&fallback=any"), Vec::<String>::new());
+        let page = get(&client, "/search?query=zzzz+-rust&fallback=any");
+        assert!(!page.contains("showing posts matching any term"));
+    }
+
+    #[test]
+    fn raw_post() {
+        let client = client(&[json!({"id": "a", "title": "Post", "selftext": "", "score": 5})]);
+        let response = client.get("/post/a/raw").dispatch();
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        let text = response.into_string().unwrap();
+        assert!(text.contains('\n'));
+        let json: Value = from_str(&text).unwrap();
+        assert_eq!(json, json!({"id": "a", "title": "Post", "selftext": "", "score": 5}));
+        assert_eq!(client.get("/post/zz/raw").dispatch().status(), Status::NotFound);
+    }
+
+    #[test]
+    fn admin_ingest() {
+        let dir = TempDir::new();
+        dir.write_posts("posts.json", &[fixture("a", "Rust jobs", "")]);
+        let mut config = config();
+        config.admin_token = Some("secret".to_string());
+        let client = serve(Arc::new(Corpus::new(vec![dir.path().to_path_buf()])), config);
+
+        let body = format!("{}\n{}\n", fixture("b", "Go jobs", ""), fixture("c", "Python jobs", ""));
+        let ingest = |uri: &str, token: &str| {
+            client.post(uri.to_string()).header(Header::new("X-Admin-Token", token.to_string())).body(&body).dispatch()
+        };
+        assert_eq!(ingest("/admin/ingest", "wrong").status(), Status::Unauthorized);
+        assert_eq!(ingest("/admin/ingest", "secre").status(), Status::Unauthorized);
+        assert_eq!(client.post("/admin/ingest").body(&body).dispatch().status(), Status::Unauthorized);
+
+        let json: Value = from_str(&ingest("/admin/ingest", "secret").into_string().unwrap()).unwrap();
+        assert_eq!(json, json!({"ingested": 2, "posts": 3}));
+        assert_eq!(search_ids(&client, "query=jobs&sort=id"), ["a", "b", "c"]);
+
+        // Files can only be ingested from the input directories
+        let outside = TempDir::new();
+        let path = outside.write_posts("more.json", &[fixture("d", "Outside", "")]);
+        let uri = format!("/admin/ingest?path={}", path.display());
+        assert_eq!(ingest(&uri, "secret").status(), Status::Forbidden);
+        let name = outside.path().file_name().unwrap().to_str().unwrap();
+        let uri = format!("/admin/ingest?path={}/../{}/more.json", dir.path().display(), name);
+        assert_eq!(ingest(&uri, "secret").status(), Status::Forbidden);
+
+        let path = dir.write_posts("more.json", &[fixture("e", "Inside", "")]);
+        let uri = format!("/admin/ingest?path={}", path.display());
+        assert_eq!(ingest(&uri, "secret").status(), Status::Ok);
+        assert_eq!(search_ids(&client, "query=inside"), ["e"]);
+        assert_eq!(search_ids(&client, "query=outside"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn allow_raw_html() {
+        let posts = [fixture("a", "Video", "## Watch\n\n<iframe src=\"https://example.com/\"></iframe>")];
+        let page = get(&client(&posts), "/post/a");
+        assert!(page.contains(r#"<h2 id="watch">Watch</h2>"#));
+        assert!(!page.contains("<iframe"));
+
+        let mut config = config();
+        config.allow_raw_html = true;
+        let page = get(&client_with(&posts, config), "/post/a");
+        assert!(page.contains(r#"<h2 id="watch">Watch</h2>"#));
+        assert!(page.contains("<iframe"));
+    }
+
+    #[test]
+    fn keyboard_navigation() {
+        let client = client(&[fixture("a", "Rust jobs", ""), fixture("b", "Career advice", "")]);
+        let nav = assets::url("nav.js");
+        for uri in ["/search?query=rust", "/candidates/2"] {
+            let page = get(&client, uri);
+            assert!(page.contains(&format!(r#"<script src="{}" defer></script>"#, nav)), "{}", uri);
+        }
+        assert!(get(&client, "/search?query=rust").contains(r#"<li data-post="a"><a href="/post/a?highlight=rust">"#));
+        assert!(get(&client, "/candidates/2").contains(r#"<li data-post="b"><a href="/post/b">"#));
+    }
+
+    #[test]
+    fn multiple_sort_keys() {
+        let post = |id: &str, subreddit: &str, score: i64| json!({"id": id, "title": "Rust", "selftext": "", "subreddit": subreddit, "score": score});
+        let client = client(&[
+            post("a", "rust", 5),
+            post("b", "learnrust", 1),
+            post("c", "rust", 50),
+            post("d", "learnrust", 10),
+            post("e", "rust", 20),
+        ]);
+        assert_eq!(search_ids(&client, "query=rust&sort=subreddit,score:desc"), ["d", "b", "c", "e", "a"]);
+        assert_eq!(search_ids(&client, "query=rust&sort=bogus,subreddit,score:asc"), ["b", "d", "a", "e", "c"]);
+        assert_eq!(search_ids(&client, "query=rust&sort=subreddit,id:desc"), ["d", "b", "e", "c", "a"]);
+        assert_eq!(search_ids(&client, "query=rust&sort=id,score"), ["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn plain_text_post() {
+        let text = "## Background\n\nI'm **learning** [Rust](https://www.rust-lang.org/).\n\n- one\n- two";
+        let client = client(&[fixture("a", "Rust <jobs>", text)]);
+        let response = client.get("/post/a.txt").dispatch();
+        assert_eq!(response.content_type(), Some(ContentType::Plain));
+        assert_eq!(
+            response.into_string().unwrap(),
+            "Rust <jobs>\n\nBackground\n\nI'm learning Rust <https://www.rust-lang.org/>.\n\n- one\n- two\n",
+        );
+        assert_eq!(client.get("/post/zz.txt").dispatch().status(), Status::NotFound);
+    }
+
+    #[test]
+    fn subreddit_facets() {
+        let post = |id: &str, subreddit: &str| json!({"id": id, "title": "Rust jobs", "selftext": "", "subreddit": subreddit});
+        let client = client(&[post("a", "rust"), post("b", "learnrust"), post("c", "rust"), post("d", "golang")]);
+        let page = get(&client, "/search?query=rust");
+        assert!(page.contains(r#"<li><a href="/search?query=rust&amp;subreddit=rust&amp;page=1">r/rust</a> (2)</li>"#));
+        assert!(page.contains(r#"<li><a href="/search?query=rust&amp;subreddit=learnrust&amp;page=1">r/learnrust</a> (1)</li>"#));
+        assert!(page.contains(r#"r/golang</a> (1)"#));
+
+        let page = get(&client, "/search?query=rust&subreddit=rust");
+        assert!(!page.contains(r#"class="facets""#));
+    }
+
+    #[test]
+    fn custom_text_fields() {
+        let words = "the quick brown fox jumps over the lazy dog while the cat sleeps on the warm mat by the door all day long";
+        let post = |id: &str, title: &str, body: &str| json!({"id": id, "title": title, "body": body});
+        let mut config = config();
+        config.text_fields = vec!["title".to_string(), "body".to_string()];
+        let client = client_with(&[
+            post("a", "Careers", "Learning **rust** at work"),
+            post("b", "Repost", words),
+            post("c", "Repost again", words),
+        ], config);
+
+        assert_eq!(search_ids(&client, "query=rust"), ["a"]);
+        assert_eq!(search_ids(&client, "query=body:rust"), ["a"]);
+        assert_eq!(search_ids(&client, "query=text:careers"), Vec::<String>::new());
+        assert!(get(&client, "/post/a").contains("<strong>rust</strong>"));
+        assert!(get(&client, "/post/a.txt").contains("Learning rust at work"));
+        assert_eq!(get(&client, "/api/posts/a/selftext"), "Learning **rust** at work");
+        assert!(get_json(&client, "/stats/wordcloud").as_array().unwrap().iter().any(|word| word["word"] == "rust"));
+        assert_eq!(get_json(&client, "/api/meta")["tokens"], 54);
+        assert_eq!(get_json(&client, "/post/b/duplicates")[0]["id"], "c");
+    }
+
+    #[test]
+    fn suggest_past_queries() {
+        let client = client(&[fixture("a", "Rust jobs", "")]);
+        get(&client, "/search?query=rust+jobs");
+        get(&client, "/search?query=Rust++Jobs");
+        get(&client, "/api/search?query=rust+careers");
+        get(&client, "/search?query=go+jobs");
+        assert_eq!(get_json(&client, "/suggest/queries?prefix=rust"), json!([
+            {"query": "rust jobs", "count": 2},
+            {"query": "rust careers", "count": 1},
+        ]));
+        assert_eq!(get_json(&client, "/suggest/queries?prefix=python"), json!([]));
+    }
+
+    #[test]
+    fn api_cap() {
+        let mut config = config();
+        config.max_api_results = 2;
+        let client = client_with(&[
+            fixture("a", "Rust", "rust rust"),
+            fixture("b", "Jobs", "rust"),
+            fixture("c", "Rust", "rust rust rust"),
+            fixture("d", "Jobs", "rust"),
+            fixture("e", "Careers", "Go"),
+        ], config);
+
+        let json = get_json(&client, "/api/search?query=rust&sort=id");
+        assert_eq!(json["truncated"], true);
+        assert_eq!(json["total"], 4);
+        assert_eq!(json["count"], 2);
+        assert_eq!(json["results"].as_array().unwrap().len(), 2);
+        assert_eq!(search_ids(&client, "query=rust&sort=id"), ["a", "c"]);
+        assert_eq!(search_ids(&client, "query=rust&sort=id:desc"), ["c", "a"]);
+
+        let json = get_json(&client, "/api/search?query=go");
+        assert_eq!(json["truncated"], false);
+    }
+
+    #[test]
+    fn scroll_cursors() {
+        let dir = TempDir::new();
+        let posts: Vec<_> = (0..25).map(|i| fixture(&format!("p{:02}", i), "Rust jobs", "")).collect();
+        dir.write_posts("a.json", &posts);
+        let corpus = Arc::new(Corpus::new(vec![dir.path().to_path_buf()]));
+        let client = serve(corpus.clone(), config());
+
+        let mut seen = vec![];
+        let mut uri = "/api/search/scroll?query=rust&per_page=10".to_string();
+        let mut cursors = vec![];
+        loop {
+            let json = get_json(&client, &uri);
+            seen.extend(json["results"].as_array().unwrap().iter().map(|item| item["id"].as_str().unwrap().to_string()));
+            match json["next_cursor"].as_str() {
+                Some(cursor) => {
+                    cursors.push(cursor.to_string());
+                    uri = format!("/api/search/scroll?query=rust&per_page=10&cursor={}", cursor);
+                }
+                None => break,
+            }
+        }
+        let expected: Vec<_> = (0..25).map(|i| format!("p{:02}", i)).collect();
+        assert_eq!(seen, expected);
+        assert_eq!(cursors.len(), 2);
+
+        // Cursors still work on another server with the same data
+        let other = serve(Arc::new(Corpus::new(vec![dir.path().to_path_buf()])), config());
+        let response = other.get(format!("/api/search/scroll?query=rust&per_page=10&cursor={}", cursors[0])).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        assert_eq!(client.get("/api/search/scroll?query=rust&cursor=bogus").dispatch().status(), Status::BadRequest);
+        dir.write_posts("b.json", &[fixture("q", "Rust careers", "")]);
+        corpus.reload().unwrap();
+        let response = client.get(format!("/api/search/scroll?query=rust&per_page=10&cursor={}", cursors[0])).dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+    }
+
+    #[test]
+    fn crosspost_parent() {
+        let client = client(&[
+            json!({
+                "id": "a",
+                "title": "Worth a read",
+                "selftext": "",
+                "crosspost_parent_list": [{"title": "Original", "selftext": "Parent *body*", "subreddit": "learnrust"}],
+            }),
+            json!({
+                "id": "b",
+                "title": "Not a crosspost",
+                "selftext": "My own body",
+                "crosspost_parent_list": [{"title": "Original", "selftext": "Parent body"}],
+            }),
+        ]);
+
+        let page = get(&client, "/post/a");
+        assert!(page.contains(r#"Crossposted from <a href="https://www.reddit.com/r/learnrust">r/learnrust</a>"#));
+        assert!(page.contains("<h2>Original</h2>"));
+        assert!(page.contains("Parent <em>body</em>"));
+
+        let page = get(&client, "/post/b");
+        assert!(!page.contains("Crossposted from"));
+        assert!(page.contains("My own body"));
+    }
+
+    #[test]
+    fn result_count() {
+        let posts: Vec<_> = (0..1234).map(|i| fixture(&format!("p{}", i), "Rust jobs", "")).collect();
+        let client = client(&posts);
+        assert!(get(&client, "/search?query=rust").contains("<h2>1,234 results for <em>rust</em></h2>"));
+
+        let mut config = config();
+        config.locale = "de-DE".to_string();
+        let client = client_with(&posts, config);
+        assert!(get(&client, "/search?query=rust").contains("<h2>1.234 results for <em>rust</em></h2>"));
+    }
+
+    #[test]
+    fn author_filter() {
+        let post = |id: &str, author: &str| json!({"id": id, "title": "Rust jobs", "selftext": "", "author": author});
+        let client = client(&[post("a", "alice"), post("b", "bob"), post("c", "Alice"), fixture("d", "Rust jobs", "")]);
+        assert_eq!(search_ids(&client, "query=rust&sort=id"), ["a", "b", "c", "d"]);
+        assert_eq!(search_ids(&client, "query=rust&sort=id&author=alice"), ["a", "c"]);
+        assert_eq!(search_ids(&client, "query=rust&sort=id&author=%20bob%20"), ["b"]);
+        assert!(search_ids(&client, "query=rust&author=carol").is_empty());
+    }
+
+    #[test]
+    fn selftext_html() {
+        let posts = [
+            json!({
+                "id": "a",
+                "title": "Rust jobs",
+                "selftext": "Hello *world*",
+                "selftext_html": "&lt;div class=\"md\"&gt;&lt;p&gt;Hello &lt;em&gt;world&lt;/em&gt;&lt;/p&gt;&lt;script&gt;alert(1)&lt;/script&gt;&lt;/div&gt;",
+            }),
+        ];
+        let html = "<div><p>Hello <em>world</em></p></div>";
+        assert!(!get(&client(&posts), "/post/a").contains(html));
+
+        let mut config = config();
+        config.prefer_selftext_html = true;
+        let client = client_with(&posts, config);
+        let page = get(&client, "/post/a");
+        assert!(page.contains(html));
+        assert!(!page.contains("alert(1)"));
+        assert!(get(&client, "/post/a?highlight=world").contains("<em><mark>world</mark></em>"));
+    }
+
+    #[test]
+    fn reindex_state() {
+        let corpus = corpus(&[fixture("a", "Rust jobs", "")]);
+        let client = serve(corpus.clone(), config());
+        let json = get_json(&client, "/status/reindex");
+        assert_eq!(json["state"], "idle");
+        assert_eq!(json["processed"], 1);
+        assert!(json["last_reload"].is_u64());
+
+        let running = corpus.status().start();
+        assert_eq!(get_json(&client, "/status/reindex")["state"], "running");
+        drop(running);
+        assert_eq!(get_json(&client, "/status/reindex")["state"], "idle");
+    }
+
+    #[test]
+    fn loose_hyphens() {
+        let posts = [fixture("a", "Self-taught dev", ""), fixture("b", "New dev", "I'm self taught")];
+        assert_eq!(search_ids(&client(&posts), "query=self-taught&sort=id"), ["a"]);
+
+        let mut config = config();
+        config.loose_hyphens = true;
+        let client = client_with(&posts, config);
+        assert_eq!(search_ids(&client, "query=self-taught&sort=id"), ["a", "b"]);
+        assert!(get(&client, "/post/b?highlight=self-taught").contains("<mark>self taught</mark>"));
+    }
+
+    #[test]
+    fn timeseries() {
+        let post = |id: &str, time: i64| json!({"id": id, "title": "Rust jobs", "selftext": "", "created_utc": time});
+        // 2021-09-30 15:00, 2021-09-30 18:00, and 2021-10-01 09:00 UTC
+        let client = client(&[
+            post("a", 1633014000),
+            post("b", 1633024800),
+            post("c", 1633078800),
+            fixture("d", "Rust jobs", ""),
+            json!({"id": "e", "title": "Go jobs", "selftext": "", "created_utc": 1633014000}),
+        ]);
+        assert_eq!(
+            get_json(&client, "/api/timeseries?query=rust&bucket=day"),
+            json!([{"date": "2021-09-30", "count": 2}, {"date": "2021-10-01", "count": 1}]),
+        );
+        assert_eq!(get_json(&client, "/api/timeseries?query=rust"), get_json(&client, "/api/timeseries?query=rust&bucket=day"));
+        assert_eq!(get_json(&client, "/api/timeseries?query=rust&bucket=month"), json!([{"date": "2021-09-01", "count": 2}, {"date": "2021-10-01", "count": 1}]));
+        assert_eq!(get_json(&client, "/api/timeseries?query=rust&bucket=week"), json!([{"date": "2021-09-27", "count": 3}]));
+        assert_eq!(client.get("/api/timeseries?query=rust&bucket=hour").dispatch().status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn punctuation_query() {
+        let client = client(&[fixture("a", "Rust jobs", ""), fixture("b", "Go jobs", "")]);
+        for query in ["***", "%3F", "%22...%22", "-%3F"] {
+            let page = get(&client, &format!("/search?query={}", query));
+            assert!(page.contains("Your query has no words to search for."), "{}", query);
+            assert!(!page.contains("<li data-post"), "{}", query);
+        }
+        assert_eq!(search_ids(&client, "query=rust%20%3F"), ["a"]);
+        assert_eq!(search_ids(&client, "query=***%20jobs&sort=id"), ["a", "b"]);
+    }
+
+    #[test]
+    fn query_id() {
+        let dir = TempDir::new();
+        dir.write_posts("a.json", &[fixture("a", "Rust jobs", ""), fixture("b", "Go jobs", "")]);
+        let corpus = Arc::new(Corpus::new(vec![dir.path().to_path_buf()]));
+        let client = serve(corpus.clone(), config());
+
+        let query_id = |uri: &str| {
+            let response = client.get(uri.to_string()).dispatch();
+            let id = response.headers().get_one("X-Pheddit-Query-Id").unwrap().to_string();
+            let page = response.into_string().unwrap();
+            assert!(page.contains(&format!("Query ID: <code>{}</code>", id)));
+            id
+        };
+
+        let id = query_id("/search?query=rust+jobs");
+        assert_eq!(id.len(), 12);
+        assert_eq!(query_id("/search?query=rust++jobs&page=2"), id);
+        assert_ne!(query_id("/search?query=rust"), id);
+        assert_ne!(query_id("/search?query=rust+jobs&sort=id"), id);
+        assert_ne!(query_id("/search?query=rust+jobs&min_match=1"), id);
+
+        // A fresh server on the same data agrees
+        assert_eq!(serve(Arc::new(Corpus::new(vec![dir.path().to_path_buf()])), config())
+            .get("/search?query=rust+jobs")
+            .dispatch()
+            .headers()
+            .get_one("X-Pheddit-Query-Id"), Some(id.as_str()));
+
+        dir.write_posts("b.json", &[fixture("c", "Rust careers", "")]);
+        corpus.reload().unwrap();
+        assert_ne!(query_id("/search?query=rust+jobs"), id);
+    }
 }
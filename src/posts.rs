@@ -0,0 +1,622 @@
+//! The in-memory post store.
+
+use crate::{candidates, date, dedup, get_str, query};
+use crate::fnv::Fnv;
+
+use rayon::prelude::*;
+
+use rocket::request::{FromRequest, Outcome, Request};
+
+use serde_json::{Value, from_str};
+
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The number of shards the posts are split across.
+const SHARDS: usize = 16;
+
+/// Make a snapshot ID for the posts loaded from some input files, which is
+/// the same for the same files in any process.
+fn snapshot_id(files: &[(PathBuf, Option<SystemTime>)]) -> u64 {
+    let mut hash = Fnv::new();
+    hash.write_u64(files.len() as u64);
+    for (path, modified) in files {
+        hash.write_str(&path.to_string_lossy());
+        match modified.and_then(|t| t.duration_since(UNIX_EPOCH).ok()) {
+            Some(d) => {
+                hash.write(&[1]);
+                hash.write_u64(d.as_secs());
+                hash.write_u64(d.subsec_nanos().into());
+            }
+            None => hash.write(&[0]),
+        }
+    }
+    hash.finish()
+}
+
+/// A loaded post, and where it came from.
+#[derive(Clone)]
+pub struct Post {
+    /// The post's JSON record.
+    pub data: Value,
+    /// The input directory the post was loaded from.
+    pub source: Arc<str>,
+    /// The input file the post was loaded from.
+    pub file: Arc<Path>,
+}
+
+impl Deref for Post {
+    type Target = Value;
+
+    fn deref(&self) -> &Value {
+        &self.data
+    }
+}
+
+impl Post {
+    /// Check whether this post came from a source directory, given either its
+    /// full path or just its name.
+    pub fn is_from(&self, source: &str) -> bool {
+        let path = Path::new(&*self.source);
+        path == Path::new(source) || path.file_name().is_some_and(|name| name == source)
+    }
+}
+
+/// All the loaded posts, by ID.
+///
+/// The posts are sharded by a hash of their ID, so the shards can be built
+/// and scanned in parallel.
+pub struct Posts {
+    shards: Vec<HashMap<String, Post>>,
+    /// Identifies this set of posts, and changes whenever they're reloaded
+    /// or modified.
+    generation: u64,
+    /// Summary statistics, computed on first use.
+    meta: OnceLock<Meta>,
+    /// The IDs of the candidate posts, computed on first use.
+    candidates: OnceLock<Vec<String>>,
+    /// The signatures of the post bodies, computed on first use.
+    signatures: OnceLock<HashMap<String, dedup::Signature>>,
+}
+
+impl Default for Posts {
+    fn default() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| HashMap::new()).collect(),
+            generation: 0,
+            meta: OnceLock::new(),
+            candidates: OnceLock::new(),
+            signatures: OnceLock::new(),
+        }
+    }
+}
+
+/// Cloning posts doesn't clone their cached statistics, so they're recomputed
+/// if the copy is modified.
+impl Clone for Posts {
+    fn clone(&self) -> Self {
+        Self {
+            shards: self.shards.clone(),
+            generation: self.generation,
+            ..Self::default()
+        }
+    }
+}
+
+/// Summary statistics about a set of posts.
+pub struct Meta {
+    pub posts: usize,
+    pub subreddits: usize,
+    pub authors: usize,
+    /// The earliest and latest `created_utc` timestamps.
+    pub earliest: Option<i64>,
+    pub latest: Option<i64>,
+    /// The total number of words in all the searched text fields.
+    pub tokens: usize,
+}
+
+impl Meta {
+    fn new(posts: &Posts, fields: &[&str]) -> Self {
+        let created = || posts.par_iter().filter_map(|post| post.get("created_utc").and_then(date::timestamp));
+        let distinct = |key| {
+            posts.par_iter()
+                .map(|post| get_str(post, key))
+                .filter(|value| !value.is_empty())
+                .collect::<HashSet<_>>()
+                .len()
+        };
+
+        Self {
+            posts: posts.len(),
+            subreddits: distinct("subreddit"),
+            authors: distinct("author"),
+            earliest: created().min(),
+            latest: created().max(),
+            tokens: posts.par_iter()
+                .map(|post| fields.iter().map(|field| query::words(get_str(post, field)).count()).sum::<usize>())
+                .sum(),
+        }
+    }
+}
+
+/// Get the shard that holds a post ID.
+fn shard(id: &str) -> usize {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    (hasher.finish() % SHARDS as u64) as usize
+}
+
+impl Posts {
+    /// Look up a post by ID.
+    pub fn get(&self, id: &str) -> Option<&Post> {
+        self.shards[shard(id)].get(id)
+    }
+
+    /// Check whether a post ID exists.
+    pub fn contains_key(&self, id: &str) -> bool {
+        self.shards[shard(id)].contains_key(id)
+    }
+
+    /// The total number of posts.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(HashMap::len).sum()
+    }
+
+    /// Add or replace a post.
+    fn insert(&mut self, id: String, post: Post) {
+        self.shards[shard(&id)].insert(id, post);
+        self.meta = OnceLock::new();
+        self.candidates = OnceLock::new();
+        self.signatures = OnceLock::new();
+    }
+
+    /// The ID of this snapshot of the posts.  Reloading or modifying the
+    /// posts gives them a new ID.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Summary statistics about these posts, counting words in the given text
+    /// fields.  Like [`Posts::candidates()`], only the first call's fields are
+    /// used.
+    pub fn meta(&self, fields: &[&str]) -> &Meta {
+        self.meta.get_or_init(|| Meta::new(self, fields))
+    }
+
+    /// The IDs of the posts to review, sorted, matching the given text fields.
+    /// See [`candidates`].  The fields come from the config and never change,
+    /// so only the first call's are used.
+    pub fn candidates(&self, fields: &[&str]) -> &[String] {
+        self.candidates.get_or_init(|| candidates::find(self, fields))
+    }
+
+    /// The signatures of the post bodies, for finding duplicates, taking the
+    /// bodies from the given text fields.  See [`dedup::signatures`].  Like
+    /// [`Posts::candidates()`], only the first call's fields are used.
+    pub fn signatures(&self, fields: &[&str]) -> &HashMap<String, dedup::Signature> {
+        self.signatures.get_or_init(|| dedup::signatures(self, fields))
+    }
+
+    /// Iterate over all the posts in parallel.
+    pub fn par_iter(&self) -> impl ParallelIterator<Item = &Post> {
+        self.shards.par_iter().flat_map(|shard| shard.par_iter().map(|(_id, post)| post))
+    }
+}
+
+impl FromParallelIterator<(String, Post)> for Posts {
+    fn from_par_iter<I>(iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = (String, Post)>,
+    {
+        iter.into_par_iter()
+            .fold(Posts::default, |mut posts, (id, post)| {
+                posts.insert(id, post);
+                posts
+            })
+            .reduce(Posts::default, |mut a, b| {
+                a.shards.par_iter_mut()
+                    .zip(b.shards)
+                    .for_each(|(a, b)| a.extend(b));
+                a
+            })
+    }
+}
+
+/// Find the input files in some directories, with their modification times.
+fn files(dirs: &[PathBuf]) -> io::Result<Vec<(PathBuf, Option<SystemTime>)>> {
+    let mut files = vec![];
+    for dir in dirs {
+        for file in fs::read_dir(dir).map_err(|e| context(dir, e))? {
+            let file = file.map_err(|e| context(dir, e))?;
+            if file.path().extension().is_some_and(|e| e == "json") {
+                files.push((file.path(), file.metadata().and_then(|m| m.modified()).ok()));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Say which file an I/O error came from.
+fn context(path: &Path, error: io::Error) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", path.display(), error))
+}
+
+/// Parse line `n` of an input file.  Records without a title aren't posts, and
+/// records that can't be parsed get a message saying where they were.
+fn parse(source: &Arc<str>, file: &Arc<Path>, n: usize, line: &str) -> Result<Option<Post>, String> {
+    match from_str::<Value>(line) {
+        // Comments have a body but no title, so don't index them as posts
+        Ok(data) if data.get("title").is_none() => Ok(None),
+        Ok(data) => Ok(Some(Post { data, source: source.clone(), file: file.clone() })),
+        Err(e) => Err(format!("skipped {}:{}: {}", file.display(), n, e)),
+    }
+}
+
+/// Load the posts from some input files, counting them in `processed`.
+fn load(paths: &[&Path], processed: &AtomicUsize) -> io::Result<Posts> {
+    let comments = AtomicUsize::new(0);
+
+    let files = paths.par_iter()
+        .map(|path| load_file(path, processed, &comments).map_err(|e| context(path, e)))
+        .collect::<io::Result<Vec<_>>>()?;
+    let posts: Posts = files.into_par_iter().flatten().collect();
+
+    let comments = comments.into_inner();
+    if comments > 0 {
+        eprintln!("skipped {} records without a title (comments?)", comments);
+    }
+
+    Ok(posts)
+}
+
+/// Load the posts from one input file, by ID.
+fn load_file(path: &Path, processed: &AtomicUsize, comments: &AtomicUsize) -> io::Result<Vec<(String, Post)>> {
+    let source: Arc<str> = path.parent().map_or("".into(), |p| p.to_string_lossy().into());
+    let file: Arc<Path> = path.into();
+
+    let mut posts = vec![];
+    // Some archives contain mojibake, so don't insist on valid UTF-8
+    for (i, line) in BufReader::new(File::open(path)?).split(b'\n').enumerate() {
+        let line = line?;
+        processed.fetch_add(1, Ordering::Relaxed);
+        match parse(&source, &file, i + 1, &String::from_utf8_lossy(&line)) {
+            Ok(Some(post)) => posts.push((get_str(&post, "id").to_string(), post)),
+            Ok(None) => {
+                comments.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+    Ok(posts)
+}
+
+/// The progress of reloading the posts.
+#[derive(Default)]
+pub struct ReindexStatus {
+    /// Whether a reload is in progress.
+    running: AtomicBool,
+    /// How many records the current or last reload has read.
+    processed: AtomicUsize,
+    /// When the last reload finished, in seconds since the epoch, or 0.
+    last_reload: AtomicU64,
+}
+
+impl ReindexStatus {
+    /// Whether a reload is in progress.
+    pub fn running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    /// How many records the current or last reload has read.
+    pub fn processed(&self) -> usize {
+        self.processed.load(Ordering::Relaxed)
+    }
+
+    /// When the last reload finished, in seconds since the epoch.
+    pub fn last_reload(&self) -> Option<u64> {
+        Some(self.last_reload.load(Ordering::Relaxed)).filter(|&t| t > 0)
+    }
+
+    /// Mark a reload as running until the returned guard is dropped, even if
+    /// the reload fails or panics.
+    pub(crate) fn start(&self) -> Running<'_> {
+        self.processed.store(0, Ordering::Relaxed);
+        self.running.store(true, Ordering::Relaxed);
+        Running(&self.running)
+    }
+}
+
+/// Clears [`ReindexStatus::running()`] when dropped.
+pub(crate) struct Running<'a>(&'a AtomicBool);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// The posts from a set of input directories, which can be reloaded while the
+/// server is running.
+pub struct Corpus {
+    dirs: Vec<PathBuf>,
+    /// The input files the current posts were loaded from.
+    files: Mutex<Vec<(PathBuf, Option<SystemTime>)>>,
+    posts: RwLock<Arc<Posts>>,
+    status: ReindexStatus,
+}
+
+impl Corpus {
+    /// Load all the posts from some directories.
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        let corpus = Self {
+            dirs,
+            files: Mutex::new(vec![]),
+            posts: RwLock::new(Arc::default()),
+            status: ReindexStatus::default(),
+        };
+        if let Err(e) = corpus.reload() {
+            eprintln!("error: couldn't load posts: {}", e);
+        }
+        if corpus.posts().len() == 0 {
+            eprintln!("warning: no posts loaded from {:?}; check the input paths", corpus.dirs);
+        }
+        corpus
+    }
+
+    /// Get the current posts.
+    pub fn posts(&self) -> Arc<Posts> {
+        self.posts.read().unwrap().clone()
+    }
+
+    /// The progress of reloading the posts.
+    pub fn status(&self) -> &ReindexStatus {
+        &self.status
+    }
+
+    /// Reload the posts if any input files have been added, removed, or
+    /// modified.  Returns whether anything changed.  If the files can't be
+    /// read, the current posts are kept.
+    pub fn reload(&self) -> io::Result<bool> {
+        let mut files = self.files.lock().unwrap();
+        let current = self::files(&self.dirs)?;
+        if *files == current {
+            return Ok(false);
+        }
+
+        let status = &self.status;
+        let running = status.start();
+        let paths: Vec<_> = current.iter().map(|(path, _)| path.as_path()).collect();
+        let loaded = load(&paths, &status.processed);
+        drop(running);
+        let mut posts = loaded?;
+        posts.generation = snapshot_id(&current);
+        eprintln!("Loaded {} posts...", posts.len());
+
+        *self.posts.write().unwrap() = Arc::new(posts);
+        *files = current;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        status.last_reload.store(now, Ordering::Relaxed);
+        Ok(true)
+    }
+
+    /// Resolve the path to a file in one of the input directories, or return
+    /// `None` if it's anywhere else (or doesn't exist).
+    pub fn input_file(&self, path: &Path) -> Option<PathBuf> {
+        let path = path.canonicalize().ok()?;
+        self.dirs.iter()
+            .filter_map(|dir| dir.canonicalize().ok())
+            .any(|dir| path.starts_with(dir))
+            .then_some(path)
+    }
+
+    /// Add posts from NDJSON text to the live posts, returning how many were
+    /// added.  Ingested posts last until the input files change and the posts
+    /// are reloaded.
+    pub fn ingest(&self, path: &Path, text: &str) -> usize {
+        let source: Arc<str> = path.parent().map_or("".into(), |p| p.to_string_lossy().into());
+        let file: Arc<Path> = path.into();
+
+        let new: Vec<_> = text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .filter_map(|(i, line)| parse(&source, &file, i + 1, line).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                None
+            }))
+            .collect();
+
+        let count = new.len();
+        let mut posts = self.posts.write().unwrap();
+        // Copies the posts only if a request is still using the old ones
+        let posts = Arc::make_mut(&mut posts);
+        for post in new {
+            posts.insert(get_str(&post, "id").to_string(), post);
+        }
+        // Derive the new ID from the old one and what was added
+        let mut hash = Fnv::new();
+        hash.write_u64(posts.generation);
+        hash.write_str(&path.to_string_lossy());
+        hash.write_str(text);
+        posts.generation = hash.finish();
+        count
+    }
+}
+
+/// A request guard for a consistent view of the posts, even if they're
+/// reloaded in the middle of the request.
+pub struct Snapshot(Arc<Posts>);
+
+impl Deref for Snapshot {
+    type Target = Posts;
+
+    fn deref(&self) -> &Posts {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Snapshot {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, ()> {
+        let corpus = req.rocket().state::<Arc<Corpus>>().unwrap();
+        Outcome::Success(Snapshot(corpus.posts()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn invalid_utf8() {
+        let dir = TempDir::new();
+        let mut text = json!({"id": "a", "title": "Before"}).to_string().into_bytes();
+        text.extend_from_slice(b"\n{\"id\": \"b\", \"title\": \"Caf\xe9\"}\n");
+        text.extend_from_slice(json!({"id": "c", "title": "After"}).to_string().as_bytes());
+        fs::write(dir.path().join("posts.json"), text).unwrap();
+
+        let corpus = Corpus::new(vec![dir.path().to_path_buf()]);
+        let posts = corpus.posts();
+        assert_eq!(posts.len(), 3);
+        assert_eq!(get_str(posts.get("b").unwrap(), "title"), "Caf\u{fffd}");
+    }
+
+    #[test]
+    fn skipped_line() {
+        let source: Arc<str> = "/dumps".into();
+        let file: Arc<Path> = Path::new("/dumps/2021.json").into();
+        let error = parse(&source, &file, 4213, "{\"id\": ").err().unwrap();
+        assert!(error.starts_with("skipped /dumps/2021.json:4213: "), "{}", error);
+
+        assert!(parse(&source, &file, 1, r#"{"id": "a", "title": "Post"}"#).unwrap().is_some());
+        assert!(parse(&source, &file, 2, r#"{"id": "b", "body": "Comment"}"#).unwrap().is_none());
+    }
+
+    #[test]
+    fn shards() {
+        let source: Arc<str> = "".into();
+        let file: Arc<Path> = Path::new("posts.json").into();
+        let baseline: HashMap<_, _> = (0..1000)
+            .map(|i| (format!("p{}", i), Post { data: json!({"id": format!("p{}", i), "title": i}), source: source.clone(), file: file.clone() }))
+            .collect();
+        let posts: Posts = baseline.clone().into_par_iter().collect();
+
+        assert_eq!(posts.len(), baseline.len());
+        assert!(posts.shards.iter().all(|shard| !shard.is_empty()));
+        for (id, post) in &baseline {
+            assert_eq!(posts.get(id).unwrap().data, post.data);
+        }
+        assert!(posts.get("p1000").is_none());
+        assert_eq!(posts.par_iter().count(), baseline.len());
+    }
+
+    #[test]
+    fn reindex() {
+        let dir = TempDir::new();
+        dir.write_posts("a.json", &[fixture("a", "First", "")]);
+        let corpus = Corpus::new(vec![dir.path().to_path_buf()]);
+        assert_eq!(corpus.posts().len(), 1);
+        assert!(!corpus.reload().unwrap());
+
+        dir.write_posts("b.json", &[fixture("b", "Second", ""), fixture("c", "Third", "")]);
+        assert!(corpus.reload().unwrap());
+        assert_eq!(corpus.posts().len(), 3);
+        assert!(!corpus.status().running());
+        assert!(corpus.status().last_reload().is_some());
+
+        // Errors keep the old posts rather than panicking
+        fs::remove_dir_all(dir.path()).unwrap();
+        assert!(corpus.reload().is_err());
+        assert_eq!(corpus.posts().len(), 3);
+        assert!(corpus.reload().is_err());
+    }
+
+    #[test]
+    fn skip_comments() {
+        let dir = TempDir::new();
+        dir.write_posts("mixed.json", &[
+            fixture("a", "A post", "Text"),
+            json!({"id": "c1", "body": "A comment", "link_id": "t3_a"}),
+            json!({"id": "c2", "body": "Another comment", "parent_id": "t1_c1"}),
+            fixture("b", "Another post", ""),
+        ]);
+        let corpus = Corpus::new(vec![dir.path().to_path_buf()]);
+        let posts = corpus.posts();
+        assert_eq!(posts.len(), 2);
+        assert!(posts.contains_key("a") && posts.contains_key("b"));
+        assert!(!posts.contains_key("c1") && !posts.contains_key("c2"));
+    }
+
+    #[test]
+    fn cached_candidates() {
+        let dir = TempDir::new();
+        dir.write_posts("a.json", &[fixture("a", "Career advice", ""), fixture("b", "Unrelated", "")]);
+        let corpus = Corpus::new(vec![dir.path().to_path_buf()]);
+        let fields = ["title", "selftext"];
+
+        let posts = corpus.posts();
+        assert!(posts.candidates.get().is_none());
+        let first = posts.candidates(&fields);
+        assert_eq!(first, ["a"]);
+        assert!(std::ptr::eq(first, posts.candidates(&fields)));
+
+        // Reloading starts a new cache
+        dir.write_posts("c.json", &[fixture("c", "Learning to code", "")]);
+        corpus.reload().unwrap();
+        let posts = corpus.posts();
+        assert!(posts.candidates.get().is_none());
+        assert_eq!(posts.candidates(&fields), ["a", "c"]);
+    }
+
+    #[test]
+    fn running_guard() {
+        let status = ReindexStatus::default();
+        let running = status.start();
+        assert!(status.running());
+        drop(running);
+        assert!(!status.running());
+
+        // A panicking reload doesn't leave the status stuck on running
+        let result = std::panic::catch_unwind(|| {
+            let _running = status.start();
+            panic!("reload failed");
+        });
+        assert!(result.is_err());
+        assert!(!status.running());
+    }
+
+    #[test]
+    fn snapshot_ids() {
+        let dir = TempDir::new();
+        dir.write_posts("a.json", &[fixture("a", "First", "")]);
+        let corpus = Corpus::new(vec![dir.path().to_path_buf()]);
+        let id = corpus.posts().generation();
+
+        // The same files give the same ID, even in another process
+        assert_eq!(Corpus::new(vec![dir.path().to_path_buf()]).posts().generation(), id);
+
+        dir.write_posts("b.json", &[fixture("b", "Second", "")]);
+        corpus.reload().unwrap();
+        let reloaded = corpus.posts().generation();
+        assert_ne!(reloaded, id);
+
+        corpus.ingest(Path::new("ingest"), &fixture("c", "Third", "").to_string());
+        assert_ne!(corpus.posts().generation(), reloaded);
+    }
+}
@@ -0,0 +1,870 @@
+//! Search query parsing and matching.
+
+use crate::get_str;
+
+use regex::{self, Regex};
+
+use serde_json::Value;
+
+use std::collections::HashSet;
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+/// How much more a match in the title counts than one in the body.
+const TITLE_WEIGHT: usize = 3;
+
+/// A parsed search query.
+#[derive(Clone, Debug)]
+pub enum Query {
+    /// A single word or "quoted phrase", and its compiled regex, which is
+    /// case-insensitive unless the phrase was "quoted"/c.
+    Term(Vec<String>, Regex),
+    /// A substring, as a case-insensitive regex matching it literally.
+    Literal(Regex),
+    /// title=="Some title": the title is exactly this (lowercase, trimmed) text.
+    ExactTitle(String),
+    /// word NEAR/n word: both words within n words of each other.
+    Near(String, String, usize),
+    /// field1,field2:query: the query matches in any of these post fields.
+    Field(Vec<&'static str>, Box<Query>),
+    /// score:>100, comments:10..50: a numeric field is within a range.
+    Range(&'static str, i64, i64),
+    /// NOT query, or -query.
+    Not(Box<Query>),
+    /// Every subquery must match.  The empty conjunction matches everything.
+    And(Vec<Query>),
+    /// At least one subquery must match.
+    Or(Vec<Query>),
+    /// At least n subqueries must match.
+    AtLeast(usize, Vec<Query>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    /// A "quoted phrase", and whether it's case-sensitive.
+    Phrase(Vec<String>, bool),
+    ExactTitle(String),
+    Field(Vec<&'static str>),
+    Range(&'static str, i64, i64),
+    And,
+    Or,
+    Not,
+    Near(usize),
+    Open,
+    Close,
+}
+
+/// The field name that body:, text:, and selftext: stand for, which matches
+/// whichever searched text fields aren't the title.
+const BODY: &str = "body";
+
+/// Get the post field for a name in a field:query, like "flair" ->
+/// "link_flair_text".
+fn field(name: &str) -> Option<&'static str> {
+    match name {
+        "title" => Some("title"),
+        "body" | "selftext" | "text" => Some(BODY),
+        "url" => Some("url"),
+        "domain" => Some("domain"),
+        "author" => Some("author"),
+        "subreddit" => Some("subreddit"),
+        "flair" => Some("link_flair_text"),
+        _ => None,
+    }
+}
+
+/// Parse the fields of a field1,field2: prefix.  Unknown fields are skipped
+/// with a warning, and if none are known, it's not a field prefix at all.
+fn fields(prefix: &str) -> Option<Vec<&'static str>> {
+    if prefix.is_empty() || !prefix.bytes().all(|b| b.is_ascii_lowercase() || b == b'_' || b == b',') {
+        return None;
+    }
+
+    let mut fields = vec![];
+    let mut unknown = vec![];
+    for name in prefix.split(',').filter(|name| !name.is_empty()) {
+        match field(name) {
+            Some(field) if !fields.contains(&field) => fields.push(field),
+            Some(_) => {}
+            None => unknown.push(name),
+        }
+    }
+
+    if fields.is_empty() {
+        return None;
+    }
+    for name in unknown {
+        eprintln!("warning: ignoring unknown field '{}'", name);
+    }
+    Some(fields)
+}
+
+/// Replace [`BODY`] in the fields of a field:query with the searched text
+/// fields other than the title.
+fn expand_body<'a>(names: &[&'a str], fields: &[&'a str]) -> Vec<&'a str> {
+    let mut expanded = vec![];
+    for &name in names {
+        if name == BODY {
+            expanded.extend(fields.iter().filter(|&&field| field != "title"));
+        } else {
+            expanded.push(name);
+        }
+    }
+    expanded
+}
+
+/// Get the numeric post field for a name in a range query.
+fn numeric_field(name: &str) -> Option<&'static str> {
+    match name {
+        "score" => Some("score"),
+        "comments" | "num_comments" => Some("num_comments"),
+        _ => None,
+    }
+}
+
+/// Parse an inclusive range like >100, <=5, 10..50, or 7.
+fn range(range: &str) -> Option<(i64, i64)> {
+    if let Some(n) = range.strip_prefix(">=") {
+        Some((n.parse().ok()?, i64::MAX))
+    } else if let Some(n) = range.strip_prefix("<=") {
+        Some((i64::MIN, n.parse().ok()?))
+    } else if let Some(n) = range.strip_prefix('>') {
+        Some((n.parse::<i64>().ok()?.checked_add(1)?, i64::MAX))
+    } else if let Some(n) = range.strip_prefix('<') {
+        Some((i64::MIN, n.parse::<i64>().ok()?.checked_sub(1)?))
+    } else if let Some((a, b)) = range.split_once("..") {
+        let a = if a.is_empty() { i64::MIN } else { a.parse().ok()? };
+        let b = if b.is_empty() { i64::MAX } else { b.parse().ok()? };
+        Some((a, b))
+    } else {
+        let n = range.parse().ok()?;
+        Some((n, n))
+    }
+}
+
+fn tokenize(query: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = query.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' {
+            chars.next();
+            tokens.push(Token::Open);
+        } else if c == ')' {
+            chars.next();
+            tokens.push(Token::Close);
+        } else if c == '-' {
+            chars.next();
+            tokens.push(Token::Not);
+        } else if c == '"' {
+            chars.next();
+            let mut phrase = String::new();
+            for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+                phrase.push(c);
+            }
+            // "Phrase"/c matches case-sensitively
+            let mut rest = chars.clone();
+            let case_sensitive = rest.next() == Some('/')
+                && rest.next() == Some('c')
+                && rest.peek().is_none_or(|&c| c.is_whitespace() || c == ')');
+            if case_sensitive {
+                chars = rest;
+            }
+
+            let words: Vec<_> = phrase.split_whitespace().map(String::from).collect();
+            if !words.is_empty() {
+                tokens.push(Token::Phrase(words, case_sensitive));
+            }
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            if let Some(title) = word.strip_prefix("title==") {
+                let mut title = title.to_string();
+                if title.is_empty() && chars.next_if_eq(&'"').is_some() {
+                    title.extend(chars.by_ref().take_while(|&c| c != '"'));
+                }
+                tokens.push(Token::ExactTitle(title.trim().to_lowercase()));
+                continue;
+            }
+
+            if let Some((prefix, rest)) = word.split_once(':') {
+                if let (Some(field), Some((lo, hi))) = (numeric_field(prefix), range(rest)) {
+                    tokens.push(Token::Range(field, lo, hi));
+                    continue;
+                }
+                if let Some(fields) = fields(prefix) {
+                    tokens.push(Token::Field(fields));
+                    if rest.is_empty() {
+                        continue;
+                    }
+                    word = rest.to_string();
+                }
+            }
+
+            let near = word.strip_prefix("NEAR/").and_then(|n| n.parse().ok());
+            tokens.push(match word.as_str() {
+                "AND" => Token::And,
+                "OR" => Token::Or,
+                "NOT" => Token::Not,
+                _ if near.is_some() => Token::Near(near.unwrap()),
+                _ => Token::Word(word),
+            });
+        }
+    }
+
+    tokens
+}
+
+/// Compile a case-insensitive regex matching the given words in sequence.
+fn regex<S: AsRef<str>>(words: &[S]) -> Option<Regex> {
+    case_regex(words, false)
+}
+
+/// Compile a regex matching the given words in sequence.
+fn case_regex<S: AsRef<str>>(words: &[S], case_sensitive: bool) -> Option<Regex> {
+    compile(words, case_sensitive, false)
+}
+
+/// Make a word's hyphens also match spaces, and its apostrophes match curly
+/// ones or nothing, as a regex.
+fn loosen(word: &str) -> String {
+    let mut pattern = String::new();
+    for c in word.chars() {
+        match c {
+            '-' => pattern += r"(?:-|\s+)",
+            '\'' | '’' => pattern += "['’]?",
+            c => pattern += &regex::escape(c.encode_utf8(&mut [0; 4])),
+        }
+    }
+    pattern
+}
+
+/// Compile a regex matching the given words in sequence, optionally loosening
+/// their hyphens and apostrophes.
+fn compile<S: AsRef<str>>(words: &[S], case_sensitive: bool, loose: bool) -> Option<Regex> {
+    let words: Vec<_> = words.iter()
+        .map(|w| if loose { loosen(w.as_ref()) } else { regex::escape(w.as_ref()) })
+        .collect();
+    let flags = if case_sensitive { "" } else { "(?i)" };
+    Regex::new(&format!(r"{}\b{}\b", flags, words.join(r"\s+"))).ok()
+}
+
+fn term(words: &[String]) -> Option<Query> {
+    case_term(words, false)
+}
+
+fn case_term(words: &[String], case_sensitive: bool) -> Option<Query> {
+    case_regex(words, case_sensitive).map(|re| Query::Term(words.to_vec(), re))
+}
+
+/// Check whether a term's regex is case-sensitive.
+fn is_case_sensitive(re: &Regex) -> bool {
+    !re.as_str().starts_with("(?i)")
+}
+
+/// Split text into lowercase words.
+pub fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Check whether a word has any letters or digits.
+fn has_alphanumeric(word: &str) -> bool {
+    word.chars().any(char::is_alphanumeric)
+}
+
+/// Remove repeated words and phrases, keeping the first occurrence.
+fn dedup(queries: Vec<Query>) -> Vec<Query> {
+    let mut seen = HashSet::new();
+    queries.into_iter()
+        .filter(|q| match q {
+            Query::Term(words, re) if is_case_sensitive(re) => seen.insert((true, words.clone())),
+            Query::Term(words, _) => {
+                seen.insert((false, words.iter().map(|w| w.to_lowercase()).collect()))
+            }
+            _ => true,
+        })
+        .collect()
+}
+
+struct Parser {
+    tokens: Peekable<IntoIter<Token>>,
+}
+
+impl Parser {
+    fn or(&mut self) -> Query {
+        let mut terms = vec![self.and()];
+        while self.tokens.next_if_eq(&Token::Or).is_some() {
+            terms.push(self.and());
+        }
+        let mut terms = dedup(terms);
+
+        if terms.len() == 1 {
+            terms.pop().unwrap()
+        } else {
+            Query::Or(terms)
+        }
+    }
+
+    fn and(&mut self) -> Query {
+        let mut terms = vec![];
+        loop {
+            match self.tokens.peek() {
+                None | Some(Token::Or) | Some(Token::Close) => break,
+                Some(Token::And) => {
+                    self.tokens.next();
+                }
+                _ => terms.extend(self.unary()),
+            }
+        }
+        let mut terms = dedup(terms);
+
+        if terms.len() == 1 {
+            terms.pop().unwrap()
+        } else {
+            Query::And(terms)
+        }
+    }
+
+    fn unary(&mut self) -> Option<Query> {
+        match self.tokens.next()? {
+            // Words of nothing but punctuation, like *** or ?, can't match anything
+            Token::Word(word) if !has_alphanumeric(&word) => None,
+            Token::Word(word) => {
+                if let Some(&Token::Near(n)) = self.tokens.peek() {
+                    self.tokens.next();
+                    if let Some(Token::Word(other)) = self.tokens.next_if(|t| matches!(t, Token::Word(_))) {
+                        return Some(Query::Near(word.to_lowercase(), other.to_lowercase(), n));
+                    }
+                }
+                term(&[word])
+            }
+            Token::Phrase(words, case_sensitive) => {
+                let words: Vec<_> = words.into_iter().filter(|w| has_alphanumeric(w)).collect();
+                if words.is_empty() {
+                    None
+                } else {
+                    case_term(&words, case_sensitive)
+                }
+            }
+            Token::ExactTitle(title) => Some(Query::ExactTitle(title)),
+            Token::Not => self.unary().map(|q| Query::Not(Box::new(q))),
+            Token::Field(fields) => self.unary().map(|q| Query::Field(fields, Box::new(q))),
+            Token::Range(field, lo, hi) => Some(Query::Range(field, lo, hi)),
+            Token::Open => {
+                let query = self.or();
+                self.tokens.next_if_eq(&Token::Close);
+                Some(query)
+            }
+            Token::And | Token::Or | Token::Near(_) | Token::Close => None,
+        }
+    }
+}
+
+/// Parse a search query.
+///
+/// Whitespace-separated words must all match, unless joined by OR.  "Quoted
+/// phrases" match consecutive words, NOT or - negates the following term, and
+/// parentheses group subqueries.  `a NEAR/n b` matches posts where the words a
+/// and b occur within n words of each other in the same field, and
+/// `title=="Some title"` matches posts with exactly that title (ignoring case).
+/// Matching ignores case, except for phrases quoted like `"Rust"/c`.  Words
+/// that are nothing but punctuation are ignored.
+/// `title,url:term` matches the term against only the listed fields, and
+/// `score:>100` or `comments:10..50` match posts with numbers in those ranges.
+///
+/// AND (explicit or implied by juxtaposition) binds tighter than OR, so
+/// `a AND b OR c` means `(a AND b) OR c`, and `a OR b c` means `a OR (b AND c)`.
+/// NOT binds tightest of all.
+pub fn parse(query: &str) -> Query {
+    let mut parser = Parser {
+        tokens: tokenize(query).into_iter().peekable(),
+    };
+
+    let mut terms = vec![parser.or()];
+    // Skip any unbalanced closing parentheses
+    while parser.tokens.next().is_some() {
+        terms.push(parser.or());
+    }
+
+    if terms.len() == 1 {
+        terms.pop().unwrap()
+    } else {
+        Query::And(terms)
+    }
+}
+
+/// Make a query that matches a literal substring, without any regex or
+/// operator processing.
+pub fn literal(query: &str) -> Query {
+    let query = query.trim();
+    if query.is_empty() {
+        Query::And(vec![])
+    } else {
+        // Escaped, the regex is a plain substring search that ignores case,
+        // without lowercasing every post
+        match Regex::new(&format!("(?i){}", regex::escape(query))) {
+            Ok(re) => Query::Literal(re),
+            Err(_) => Query::And(vec![]),
+        }
+    }
+}
+
+impl Query {
+    /// Check whether this query matches any of the given text fields.  The
+    /// first field is the title.
+    fn matches_fields(&self, fields: &[&str]) -> bool {
+        match self {
+            Self::ExactTitle(title) => fields.first().is_some_and(|f| f.trim().to_lowercase() == *title),
+            Self::Field(_, query) => query.matches_fields(fields),
+            Self::Range(..) => false,
+            Self::Term(_, re) | Self::Literal(re) => fields.iter().any(|f| re.is_match(f)),
+            Self::Near(a, b, n) => fields.iter().any(|f| {
+                let mut last_a = None;
+                let mut last_b = None;
+                for (i, word) in words(f).enumerate() {
+                    if word == *a {
+                        last_a = Some(i);
+                    }
+                    if word == *b {
+                        last_b = Some(i);
+                    }
+                    if let (Some(i), Some(j)) = (last_a, last_b) {
+                        if i.abs_diff(j) <= *n {
+                            return true;
+                        }
+                    }
+                }
+                false
+            }),
+            Self::Not(query) => !query.matches_fields(fields),
+            Self::And(queries) => queries.iter().all(|q| q.matches_fields(fields)),
+            Self::Or(queries) => queries.iter().any(|q| q.matches_fields(fields)),
+            Self::AtLeast(n, queries) => {
+                queries.iter().filter(|q| q.matches_fields(fields)).count() >= *n
+            }
+        }
+    }
+
+    /// Check whether this query only narrows down the matches of the rest of
+    /// the query, like -word or score:>100, rather than matching terms itself.
+    fn is_filter(&self) -> bool {
+        match self {
+            Self::Not(_) | Self::Range(..) => true,
+            Self::Field(_, query) => query.is_filter(),
+            _ => false,
+        }
+    }
+
+    /// Count the top-level terms of this query, not including filters like
+    /// -word or score:>100.
+    pub fn term_count(&self) -> usize {
+        match self {
+            Self::And(queries) => queries.iter().filter(|q| !q.is_filter()).count(),
+            query if query.is_filter() => 0,
+            _ => 1,
+        }
+    }
+
+    /// Relax a query so only `n` of its top-level terms need to match.  Filters
+    /// like -word or score:>100 still have to match.
+    pub fn at_least(self, n: usize) -> Self {
+        let terms = self.term_count();
+        match self {
+            Self::And(queries) if n < terms => {
+                let (mut filters, terms): (Vec<_>, Vec<_>) = queries.into_iter().partition(Self::is_filter);
+                let relaxed = Self::AtLeast(n.max(1), terms);
+                if filters.is_empty() {
+                    relaxed
+                } else {
+                    filters.insert(0, relaxed);
+                    Self::And(filters)
+                }
+            }
+            query => query,
+        }
+    }
+
+    /// Remove single-word terms for which `drop` returns true.  Phrases and
+    /// case-sensitive terms are kept intact.
+    pub fn without_words<F: Fn(&str) -> bool>(self, drop: &F) -> Self {
+        let keep = |q: &Query| match q {
+            Self::Term(words, re) => words.len() != 1 || is_case_sensitive(re) || !drop(&words[0]),
+            _ => true,
+        };
+        let filter = |queries: Vec<Query>| -> Vec<Query> {
+            queries.into_iter()
+                .filter(keep)
+                .map(|q| q.without_words(drop))
+                .filter(|q| !q.is_empty())
+                .collect()
+        };
+
+        match self {
+            // Negating nothing would exclude every post, so drop the negation too
+            Self::Not(query) => match query.without_words(drop) {
+                query if query.is_empty() => Self::And(vec![]),
+                query => Self::Not(Box::new(query)),
+            },
+            Self::Field(fields, query) => match query.without_words(drop) {
+                query if query.is_empty() => query,
+                query => Self::Field(fields, Box::new(query)),
+            },
+            Self::And(queries) => Self::And(filter(queries)),
+            Self::Or(queries) => Self::Or(filter(queries)),
+            Self::AtLeast(n, queries) => Self::AtLeast(n, filter(queries)),
+            query if !keep(&query) => Self::And(vec![]),
+            query => query,
+        }
+    }
+
+    /// Let hyphenated words in terms match whether they're written with a
+    /// hyphen or as separate words, so `self-taught` matches "self taught"
+    /// too.  Apostrophes can also be curly or left out, like "dont".
+    pub fn loosen_hyphens(self) -> Self {
+        let loosen = |queries: Vec<Query>| queries.into_iter().map(Self::loosen_hyphens).collect();
+
+        match self {
+            Self::Term(words, re) => match compile(&words, is_case_sensitive(&re), true) {
+                Some(loose) => Self::Term(words, loose),
+                None => Self::Term(words, re),
+            },
+            Self::Not(query) => Self::Not(Box::new(query.loosen_hyphens())),
+            Self::Field(fields, query) => Self::Field(fields, Box::new(query.loosen_hyphens())),
+            Self::And(queries) => Self::And(loosen(queries)),
+            Self::Or(queries) => Self::Or(loosen(queries)),
+            Self::AtLeast(n, queries) => Self::AtLeast(n, loosen(queries)),
+            query => query,
+        }
+    }
+
+    /// Remove single-word terms that are stop words.
+    pub fn without_stop_words(self, stop_words: &[&str]) -> Self {
+        self.without_words(&|word| stop_words.contains(&word.to_lowercase().as_str()))
+    }
+
+    /// Check whether this is the empty query.
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Self::And(queries) if queries.is_empty())
+    }
+
+    /// Check whether this query matches any of the named fields of a post.
+    /// The first field should be the title.
+    pub fn matches_in(&self, post: &Value, fields: &[&str]) -> bool {
+        match self {
+            Self::Field(names, query) => query.matches_in(post, &expand_body(names, fields)),
+            Self::Not(query) => !query.matches_in(post, fields),
+            Self::And(queries) => queries.iter().all(|q| q.matches_in(post, fields)),
+            Self::Or(queries) => queries.iter().any(|q| q.matches_in(post, fields)),
+            Self::AtLeast(n, queries) => {
+                queries.iter().filter(|q| q.matches_in(post, fields)).count() >= *n
+            }
+            Self::ExactTitle(_) => self.matches_fields(&[get_str(post, "title")]),
+            Self::Range(field, lo, hi) => {
+                post.get(field)
+                    .and_then(Value::as_i64)
+                    .is_some_and(|n| *lo <= n && n <= *hi)
+            }
+            _ => {
+                let values: Vec<_> = fields.iter().map(|f| get_str(post, f)).collect();
+                self.matches_fields(&values)
+            }
+        }
+    }
+
+    /// Count the weighted matches of this query in a title and body.
+    fn score_fields(&self, title: &str, text: &str) -> usize {
+        match self {
+            Self::Term(_, re) | Self::Literal(re) => {
+                TITLE_WEIGHT * re.find_iter(title).count() + re.find_iter(text).count()
+            }
+            Self::ExactTitle(_) => {
+                if self.matches_fields(&[title]) {
+                    TITLE_WEIGHT
+                } else {
+                    0
+                }
+            }
+            Self::Near(..) => {
+                if self.matches_fields(&[title]) {
+                    TITLE_WEIGHT
+                } else if self.matches_fields(&[text]) {
+                    1
+                } else {
+                    0
+                }
+            }
+            Self::Not(_) | Self::Range(..) => 0,
+            // Only count the title and body, if they're in scope
+            Self::Field(fields, query) => {
+                let title = if fields.contains(&"title") { title } else { "" };
+                let text = if fields.contains(&BODY) { text } else { "" };
+                query.score_fields(title, text)
+            }
+            Self::And(queries) | Self::Or(queries) | Self::AtLeast(_, queries) => {
+                queries.iter().map(|q| q.score_fields(title, text)).sum()
+            }
+        }
+    }
+
+    /// Score a post's relevance to this query, counting matches in the named
+    /// fields.  Higher is better.
+    pub fn score_in(&self, post: &Value, fields: &[&str]) -> usize {
+        fields.iter()
+            .map(|&field| match field {
+                "title" => self.score_fields(get_str(post, field), ""),
+                _ => self.score_fields("", get_str(post, field)),
+            })
+            .sum()
+    }
+
+    /// Collect the (non-negated) terms of this query.
+    fn terms(&self, terms: &mut Vec<String>) {
+        match self {
+            Self::Term(_, re) | Self::Literal(re) => terms.push(re.as_str().to_string()),
+            Self::ExactTitle(s) => terms.push(format!("(?i){}", regex::escape(s))),
+            Self::Near(a, b, _) => {
+                terms.extend(regex(&[a]).map(|re| re.as_str().to_string()));
+                terms.extend(regex(&[b]).map(|re| re.as_str().to_string()));
+            }
+            Self::Not(_) | Self::Range(..) => {}
+            Self::Field(_, query) => query.terms(terms),
+            Self::And(queries) | Self::Or(queries) | Self::AtLeast(_, queries) => {
+                for query in queries {
+                    query.terms(terms);
+                }
+            }
+        }
+    }
+
+    /// Get a regex that matches any of the terms of this query, for highlighting.
+    pub fn highlighter(&self) -> Option<Regex> {
+        let mut terms = vec![];
+        self.terms(&mut terms);
+        if terms.is_empty() {
+            return None;
+        }
+
+        let terms: Vec<_> = terms.iter()
+            .map(|re| format!("(?:{})", re))
+            .collect();
+        Regex::new(&terms.join("|")).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Show a query's structure, like `(and rust (or jobs careers))`.
+    fn show(query: &Query) -> String {
+        let list = |op: &str, queries: &[Query]| {
+            let queries: Vec<_> = queries.iter().map(show).collect();
+            format!("({} {})", op, queries.join(" "))
+        };
+
+        match query {
+            Query::Term(words, re) if is_case_sensitive(re) => format!("\"{}\"/c", words.join(" ")),
+            Query::Term(words, _) if words.len() > 1 => format!("\"{}\"", words.join(" ")),
+            Query::Term(words, _) => words[0].clone(),
+            Query::Literal(re) => format!("literal:{}", re),
+            Query::ExactTitle(title) => format!("title=={}", title),
+            Query::Near(a, b, n) => format!("(near/{} {} {})", n, a, b),
+            Query::Field(fields, query) => format!("{}:{}", fields.join(","), show(query)),
+            Query::Range(field, lo, hi) => format!("{}:{}..{}", field, lo, hi),
+            Query::Not(query) => format!("-{}", show(query)),
+            Query::And(queries) => list("and", queries),
+            Query::Or(queries) => list("or", queries),
+            Query::AtLeast(n, queries) => list(&format!("atleast/{}", n), queries),
+        }
+    }
+
+    fn parsed(query: &str) -> String {
+        show(&parse(query))
+    }
+
+    #[test]
+    fn parse_boolean() {
+        assert_eq!(parsed("rust"), "rust");
+        assert_eq!(parsed("rust jobs"), "(and rust jobs)");
+        assert_eq!(parsed("rust AND jobs"), "(and rust jobs)");
+        assert_eq!(parsed("rust OR go"), "(or rust go)");
+        assert_eq!(parsed("rust -go"), "(and rust -go)");
+        assert_eq!(parsed("rust NOT go"), "(and rust -go)");
+        assert_eq!(parsed("\"self taught\" (rust OR go)"), "(and \"self taught\" (or rust go))");
+        assert_eq!(parsed("-(rust OR go)"), "-(or rust go)");
+        assert_eq!(parsed(""), "(and )");
+    }
+
+    #[test]
+    fn parse_unbalanced() {
+        assert_eq!(parsed("(rust OR go"), "(or rust go)");
+        assert_eq!(parsed("rust) go"), "(and rust go)");
+    }
+
+    const FIELDS: &[&str] = &["title", "selftext"];
+
+    fn matches(query: &str, title: &str, body: &str) -> bool {
+        let post = serde_json::json!({"title": title, "selftext": body});
+        parse(query).matches_in(&post, FIELDS)
+    }
+
+    #[test]
+    fn near() {
+        assert_eq!(parsed("rust NEAR/3 jobs"), "(near/3 rust jobs)");
+        assert!(matches("rust NEAR/3 jobs", "", "rust dev jobs"));
+        assert!(matches("rust NEAR/3 jobs", "", "jobs for rust"));
+        assert!(!matches("rust NEAR/3 jobs", "", "rust is great for many jobs"));
+        // Both words have to be in the same field
+        assert!(!matches("rust NEAR/3 jobs", "rust", "jobs"));
+    }
+
+    #[test]
+    fn at_least() {
+        let min_match = |query: &str, n| show(&parse(query).at_least(n));
+        assert_eq!(min_match("a b c", 2), "(atleast/2 a b c)");
+        assert_eq!(min_match("a b c", 3), "(and a b c)");
+        assert_eq!(min_match("a b c", 0), "(atleast/1 a b c)");
+        assert_eq!(min_match("a b -c", 1), "(and (atleast/1 a b) -c)");
+        assert_eq!(min_match("a -b", 1), "(and a -b)");
+        assert_eq!(min_match("a b score:>100", 1), "(and (atleast/1 a b) score:101..9223372036854775807)");
+
+        let query = parse("rust go java -python").at_least(2);
+        let post = |title| serde_json::json!({"title": title, "selftext": ""});
+        assert!(query.matches_in(&post("rust and go"), FIELDS));
+        assert!(!query.matches_in(&post("rust alone"), FIELDS));
+        assert!(!query.matches_in(&post("rust and go and python"), FIELDS));
+    }
+
+    #[test]
+    fn title_weight() {
+        let query = parse("rust");
+        let title = serde_json::json!({"title": "Rust", "selftext": "nothing here"});
+        let body = serde_json::json!({"title": "Help", "selftext": "rust rust"});
+        assert!(query.score_in(&title, FIELDS) > query.score_in(&body, FIELDS));
+    }
+
+    #[test]
+    fn literal_mode() {
+        let query = literal("  C++ (beta)?  ");
+        assert_eq!(show(&query), r"literal:(?i)C\+\+ \(beta\)\?");
+        let post = |body| serde_json::json!({"title": "", "selftext": body});
+        assert!(query.matches_in(&post("learning c++ (BETA)? today"), FIELDS));
+        assert!(!query.matches_in(&post("learning c (beta)"), FIELDS));
+        assert!(!query.matches_in(&post("learning c++ beta"), FIELDS));
+        assert_eq!(query.score_in(&post("c++ (beta)? and C++ (Beta)?"), FIELDS), 2);
+        assert!(literal("   ").is_empty());
+    }
+
+    #[test]
+    fn repeated_words() {
+        assert_eq!(parsed("rust rust programming"), "(and rust programming)");
+        assert_eq!(parsed("Rust rust"), "Rust");
+        assert_eq!(parsed("\"Rust\"/c rust"), "(and \"Rust\"/c rust)");
+        assert_eq!(parsed("rust OR rust"), "rust");
+        assert_eq!(parse("rust RUST programming").highlighter().unwrap().as_str().matches("rust").count(), 1);
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(parsed("a AND b OR c"), "(or (and a b) c)");
+        assert_eq!(parsed("a OR b AND c"), "(or a (and b c))");
+        assert_eq!(parsed("a OR b c"), "(or a (and b c))");
+
+        // Only the first reading matches a post with just c
+        assert!(matches("alpha AND beta OR gamma", "gamma", ""));
+        assert!(!matches("alpha AND beta OR gamma", "beta", ""));
+        // Only the second reading matches a post with just a
+        assert!(matches("alpha OR beta AND gamma", "alpha", ""));
+        assert!(!matches("alpha OR beta AND gamma", "gamma", ""));
+    }
+
+    #[test]
+    fn exact_title() {
+        assert_eq!(parsed(r#"title=="Rust jobs""#), "title==rust jobs");
+        assert!(matches(r#"title=="Rust jobs""#, "Rust jobs", ""));
+        assert!(matches(r#"title=="Rust jobs""#, "  rust JOBS ", ""));
+        assert!(!matches(r#"title=="Rust jobs""#, "Rust jobs in Berlin", ""));
+        assert!(matches(r#"title:"Rust jobs""#, "Rust jobs in Berlin", ""));
+        assert!(!matches(r#"title=="Rust jobs""#, "", "Rust jobs"));
+    }
+
+    #[test]
+    fn field_lists() {
+        assert_eq!(parsed("title,url:foo"), "title,url:foo");
+        assert_eq!(parsed("title,bogus:foo"), "title:foo");
+        assert_eq!(parsed("bogus:foo"), "bogus:foo");
+
+        let post = |title: &str, url: &str| serde_json::json!({"title": title, "selftext": "foo", "url": url});
+        let query = parse("title,url:foo");
+        assert!(query.matches_in(&post("Bar", "https://foo.example/"), FIELDS));
+        assert!(query.matches_in(&post("Foo", "https://bar.example/"), FIELDS));
+        assert!(!query.matches_in(&post("Bar", "https://bar.example/"), FIELDS));
+    }
+
+    #[test]
+    fn ranges() {
+        let post = |score: i64, comments: i64| serde_json::json!({"title": "rust", "selftext": "", "score": score, "num_comments": comments});
+        let query = parse("rust score:>100");
+        assert!(!query.matches_in(&post(100, 0), FIELDS));
+        assert!(query.matches_in(&post(101, 0), FIELDS));
+        assert!(!parse("go score:>100").matches_in(&post(101, 0), FIELDS));
+
+        let query = parse("comments:10..50");
+        assert!(!query.matches_in(&post(0, 9), FIELDS));
+        assert!(query.matches_in(&post(0, 10), FIELDS));
+        assert!(query.matches_in(&post(0, 50), FIELDS));
+        assert!(!query.matches_in(&post(0, 51), FIELDS));
+
+        assert!(parse("score:>=5").matches_in(&post(5, 0), FIELDS));
+        assert!(!parse("score:<5").matches_in(&post(5, 0), FIELDS));
+        assert!(parse("score:<=5").matches_in(&post(5, 0), FIELDS));
+    }
+
+    #[test]
+    fn case_sensitive_phrases() {
+        assert_eq!(parsed(r#""IT"/c jobs"#), r#"(and "IT"/c jobs)"#);
+        assert!(matches(r#""IT"/c"#, "IT department", ""));
+        assert!(!matches(r#""IT"/c"#, "fit", ""));
+        assert!(!matches(r#""IT"/c"#, "it department", ""));
+        assert!(matches(r#""IT"/c JOBS"#, "IT jobs", ""));
+    }
+
+    #[test]
+    fn loose_hyphens() {
+        let loose = |query: &str, body: &str| {
+            let post = serde_json::json!({"title": "", "selftext": body});
+            parse(query).loosen_hyphens().matches_in(&post, FIELDS)
+        };
+        assert!(loose("self-taught", "I'm self-taught"));
+        assert!(loose("self-taught", "I'm self taught"));
+        assert!(loose("\"self-taught dev\"", "a self taught dev"));
+        assert!(!loose("self-taught", "I taught myself"));
+        assert!(loose("don't", "I dont know"));
+        assert!(loose("don't", "I don’t know"));
+        assert!(!matches("self-taught", "", "I'm self taught"));
+    }
+
+    #[test]
+    fn punctuation_words() {
+        assert_eq!(parsed("*** ?"), "(and )");
+        assert!(parse("*** ?").is_empty());
+        assert!(parse("\"...\"").is_empty());
+        assert_eq!(parsed("rust *** jobs ?"), "(and rust jobs)");
+        assert_eq!(parsed("\"rust ... jobs\""), "\"rust jobs\"");
+        assert!(matches("rust ?", "Rust jobs", ""));
+    }
+}
@@ -0,0 +1,679 @@
+//! Markdown rendering for post bodies.
+
+use pulldown_cmark::{Event, Parser, Tag, html};
+
+use regex::Regex;
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+/// Bare URLs, and reddit-style /r/sub and /u/user references.
+static LINKS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?P<url>https?://[^\s<>"]*[^\s<>"'.,;:!?)\]])|(?:^|[^\w/])(?P<ref>/?(?P<kind>[ru])/(?P<name>[\w-]+))"#)
+        .unwrap()
+});
+
+/// Reddit's >!spoiler!< syntax.
+static SPOILERS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r">!(.+?)!<").unwrap());
+
+/// HTML tags and comments.
+static TAGS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)<!--.*?-->|<(/?)([a-zA-Z][a-zA-Z0-9]*)([^>]*)>").unwrap()
+});
+
+/// The href attribute of an HTML tag.
+static HREF: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\bhref\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap()
+});
+
+/// The HTML tags that [`sanitize_html()`] keeps, without any attributes except
+/// for links.
+const ALLOWED_TAGS: &[&str] = &[
+    "a", "blockquote", "br", "code", "del", "div", "em", "h1", "h2", "h3", "h4", "h5", "h6",
+    "hr", "li", "ol", "p", "pre", "span", "strong", "sup", "table", "tbody", "td", "th",
+    "thead", "tr", "ul",
+];
+
+/// Link targets that are safe to keep.
+const ALLOWED_SCHEMES: &[&str] = &["http://", "https://", "mailto:", "/", "#"];
+
+fn escape_chars(text: &str, quotes: bool) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => output += "&amp;",
+            '<' => output += "&lt;",
+            '>' => output += "&gt;",
+            '"' if quotes => output += "&quot;",
+            _ => output.push(c),
+        }
+    }
+    output
+}
+
+/// Escape text for use as HTML element content.
+pub fn escape(text: &str) -> String {
+    escape_chars(text, false)
+}
+
+/// Escape text for use in a double-quoted HTML attribute.
+pub fn escape_attr(text: &str) -> String {
+    escape_chars(text, true)
+}
+
+/// The thousands separator for a locale like en-US.
+fn thousands_separator(locale: &str) -> &'static str {
+    let lang = locale.split(['-', '_']).next().unwrap_or("");
+    match lang.to_ascii_lowercase().as_str() {
+        "da" | "de" | "es" | "id" | "it" | "nl" | "pt" | "tr" => ".",
+        "cs" | "fi" | "fr" | "nb" | "pl" | "ru" | "sv" | "uk" => "\u{a0}",
+        _ => ",",
+    }
+}
+
+/// Format a count with thousands separators for a locale, like 12,340.
+pub fn format_count(n: usize, locale: &str) -> String {
+    let digits = n.to_string();
+    let separator = thousands_separator(locale);
+    let mut output = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            output += separator;
+        }
+        output.push(digit);
+    }
+    output
+}
+
+/// Decode the HTML entities that Reddit uses to escape `selftext_html`.
+pub fn unescape(text: &str) -> String {
+    // &amp; goes last, so &amp;lt; becomes &lt; rather than <
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Sanitize untrusted HTML, like Reddit's `selftext_html`, and highlight any
+/// matches of `re` in its text.  Only a few formatting tags are kept, links
+/// keep only safe hrefs, and everything else is dropped or escaped.
+pub fn sanitize_html(html: &str, re: Option<&Regex>) -> String {
+    let mut output = String::new();
+    let mut last = 0;
+    // The contents of <script> and <style> are dropped entirely
+    let mut skip: Option<String> = None;
+
+    for caps in TAGS.captures_iter(html) {
+        let m = caps.get(0).unwrap();
+        if skip.is_none() {
+            output += &highlight(&unescape(&html[last..m.start()]), re);
+        }
+        last = m.end();
+
+        // Comments have no name
+        let name = match caps.get(2) {
+            Some(name) => name.as_str().to_ascii_lowercase(),
+            None => continue,
+        };
+        let closing = &caps[1] == "/";
+
+        if let Some(tag) = &skip {
+            if closing && *tag == name {
+                skip = None;
+            }
+            continue;
+        }
+        if name == "script" || name == "style" {
+            if !closing {
+                skip = Some(name);
+            }
+            continue;
+        }
+        if !ALLOWED_TAGS.contains(&name.as_str()) {
+            continue;
+        }
+
+        if closing {
+            output += &format!("</{}>", name);
+        } else if name == "a" {
+            let href = HREF.captures(&caps[3])
+                .and_then(|href| href.get(1).or_else(|| href.get(2)))
+                .map(|href| unescape(href.as_str()))
+                .filter(|href| ALLOWED_SCHEMES.iter().any(|s| href.to_ascii_lowercase().starts_with(s)));
+            match href {
+                Some(href) => output += &format!(r#"<a href="{}">"#, escape_attr(&href)),
+                None => output += "<a>",
+            }
+        } else {
+            output += &format!("<{}>", name);
+        }
+    }
+
+    if skip.is_none() {
+        output += &highlight(&unescape(&html[last..]), re);
+    }
+    output
+}
+
+/// Wrap every match of `re` in `text` with <mark>, escaping the rest.
+pub fn highlight(text: &str, re: Option<&Regex>) -> String {
+    let re = match re {
+        Some(re) => re,
+        None => return escape(text),
+    };
+
+    let mut output = String::new();
+    let mut last = 0;
+    for m in re.find_iter(text) {
+        output += &escape(&text[last..m.start()]);
+        output += "<mark>";
+        output += &escape(m.as_str());
+        output += "</mark>";
+        last = m.end();
+    }
+    output += &escape(&text[last..]);
+    output
+}
+
+/// Get the byte offset `n` characters after `start` in `text`, or the end.
+fn chars_after(text: &str, start: usize, n: usize) -> usize {
+    text[start..].char_indices().nth(n).map_or(text.len(), |(i, _)| start + i)
+}
+
+/// Get the byte offset `n` characters before `end` in `text`, or the start.
+fn chars_before(text: &str, end: usize, n: usize) -> usize {
+    if n == 0 {
+        return end;
+    }
+    text[..end].char_indices().rev().nth(n - 1).map_or(0, |(i, _)| i)
+}
+
+/// Get up to `fragments` non-overlapping excerpts of `text` around matches of
+/// `re`, each about `len` characters long, highlighted and joined by ellipses.
+pub fn snippet(text: &str, re: Option<&Regex>, len: usize, fragments: usize) -> String {
+    // Each fragment starts a little before its match
+    let mut windows: Vec<(usize, usize)> = vec![];
+    for m in re.iter().flat_map(|re| re.find_iter(text)) {
+        if windows.len() >= fragments {
+            break;
+        }
+        if windows.last().is_some_and(|&(_, end)| m.start() < end) {
+            continue;
+        }
+        let start = chars_before(text, m.start(), len / 4);
+        let start = windows.last().map_or(start, |&(_, end)| start.max(end));
+        windows.push((start, chars_after(text, start, len)));
+    }
+    if windows.is_empty() {
+        windows.push((0, chars_after(text, 0, len)));
+    }
+
+    let mut output = String::new();
+    let mut last = 0;
+    for (start, end) in windows {
+        if start > last {
+            output += "…";
+        }
+        output += &highlight(&text[start..end], re);
+        last = end;
+    }
+    if last < text.len() {
+        output += "…";
+    }
+    output
+}
+
+/// Turn bare URLs and /r/, /u/ references into links.
+fn autolink(text: &str, re: Option<&Regex>) -> String {
+    let mut output = String::new();
+    let mut last = 0;
+    for caps in LINKS.captures_iter(text) {
+        let (m, href) = if let Some(url) = caps.name("url") {
+            (url, url.as_str().to_string())
+        } else {
+            let kind = if &caps["kind"] == "r" { "r" } else { "user" };
+            (caps.name("ref").unwrap(), format!("https://www.reddit.com/{}/{}", kind, &caps["name"]))
+        };
+
+        output += &highlight(&text[last..m.start()], re);
+        output += &format!(r#"<a href="{}">{}</a>"#, escape_attr(&href), highlight(m.as_str(), re));
+        last = m.end();
+    }
+    output += &highlight(&text[last..], re);
+    output
+}
+
+/// Hide spoilers until they're hovered or focused, and autolink the rest.
+fn spoilers(text: &str, re: Option<&Regex>) -> String {
+    let mut output = String::new();
+    let mut last = 0;
+    for caps in SPOILERS.captures_iter(text) {
+        let m = caps.get(0).unwrap();
+        output += &autolink(&text[last..m.start()], re);
+        output += r#"<span class="spoiler" tabindex="0">"#;
+        output += &autolink(&caps[1], re);
+        output += "</span>";
+        last = m.end();
+    }
+    output += &autolink(&text[last..], re);
+    output
+}
+
+/// Markdown would parse a >!spoiler!< at the start of a line as a block quote,
+/// so escape those (outside of code blocks).
+fn escape_spoilers(text: &str) -> Cow<'_, str> {
+    if !text.contains(">!") {
+        return Cow::Borrowed(text);
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut fence = None;
+    for line in text.split_inclusive('\n') {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let trimmed = &line[indent..];
+
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+        } else if trimmed.starts_with("```") {
+            fence = Some("```");
+        } else if trimmed.starts_with("~~~") {
+            fence = Some("~~~");
+        } else if indent < 4 && trimmed.starts_with(">!") {
+            output += &line[..indent];
+            output += "\\";
+            output += trimmed;
+            continue;
+        }
+
+        output += line;
+    }
+    Cow::Owned(output)
+}
+
+/// Count how many levels of block quote a line is nested in.
+fn quote_depth(line: &str) -> usize {
+    let mut rest = line.strip_suffix('\n').unwrap_or(line);
+    let mut depth = 0;
+    loop {
+        let trimmed = rest.trim_start_matches(' ');
+        // The first > may be indented by up to three spaces
+        if depth == 0 && rest.len() - trimmed.len() > 3 {
+            return 0;
+        }
+        match trimmed.strip_prefix('>') {
+            Some(inner) => {
+                depth += 1;
+                rest = inner;
+            }
+            None => return depth,
+        }
+    }
+}
+
+/// Reddit treats quotes separated only by blank lines as one quote with
+/// several paragraphs, where markdown would start a new quote for each one.
+/// So quote the blank lines too (outside of code blocks).
+fn join_quotes(text: &str) -> Cow<'_, str> {
+    if !text.contains('>') {
+        return Cow::Borrowed(text);
+    }
+
+    let lines: Vec<_> = text.split_inclusive('\n').collect();
+    let mut output = String::with_capacity(text.len());
+    let mut fence = None;
+    let mut depth = 0;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim_start_matches(' ');
+
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+        } else if trimmed.starts_with("```") {
+            fence = Some("```");
+        } else if trimmed.starts_with("~~~") {
+            fence = Some("~~~");
+        } else if line.trim().is_empty() && depth > 0 {
+            let end = (i..lines.len()).find(|&j| !lines[j].trim().is_empty()).unwrap_or(lines.len());
+            let next = lines.get(end).map_or(0, |line| quote_depth(line));
+            let joined = depth.min(next);
+            for _ in i..end {
+                output += &">".repeat(joined);
+                output += "\n";
+            }
+            i = end;
+            continue;
+        }
+
+        depth = if fence.is_some() { 0 } else { quote_depth(line) };
+        output += line;
+        i += 1;
+    }
+    Cow::Owned(output)
+}
+
+/// Posts with at least this many headings get a table of contents.
+const TOC_MIN_HEADINGS: usize = 3;
+
+/// Make a URL-safe slug for a heading, like "Getting started" -> "getting-started".
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if (c.is_whitespace() || c == '-' || c == '_') && !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+    if slug.is_empty() {
+        slug.push_str("section");
+    }
+    slug
+}
+
+/// Give each heading a unique id, returning the (level, id, text) of each one.
+fn anchor_headings(events: &mut [Event<'_>]) -> Vec<(u32, String, String)> {
+    let mut headings = vec![];
+    let mut seen = HashSet::new();
+
+    let mut i = 0;
+    while i < events.len() {
+        let level = match events[i] {
+            Event::Start(Tag::Heading(level)) => level,
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+
+        let mut text = String::new();
+        let mut j = i + 1;
+        while j < events.len() {
+            match &events[j] {
+                Event::End(Tag::Heading(_)) => break,
+                Event::Text(t) | Event::Code(t) => text += t,
+                _ => {}
+            }
+            j += 1;
+        }
+
+        // Append -2, -3, etc. to repeated slugs
+        let base = slugify(&text);
+        let mut id = base.clone();
+        let mut n = 1;
+        while !seen.insert(id.clone()) {
+            n += 1;
+            id = format!("{}-{}", base, n);
+        }
+
+        events[i] = Event::Html(format!(r#"<h{} id="{}">"#, level, id).into());
+        headings.push((level, id, text));
+        i = j;
+    }
+
+    headings
+}
+
+/// Render a collapsible table of contents.
+fn toc(headings: &[(u32, String, String)]) -> String {
+    let mut output = String::from(r#"<details class="toc"><summary>Contents</summary><ul>"#);
+    for (level, id, text) in headings {
+        output += &format!(
+            r##"<li class="toc-{}"><a href="#{}">{}</a></li>"##,
+            level,
+            id,
+            escape(text),
+        );
+    }
+    output += "</ul></details>\n";
+    output
+}
+
+/// Render a markdown post body to HTML, highlighting any matches of `re`.
+pub fn markdown(text: &str, re: Option<&Regex>, raw_html: bool) -> String {
+    let text = escape_spoilers(text);
+    let text = join_quotes(&text);
+
+    // Markdown splits text at special characters, so join it back up
+    let mut events: Vec<Event<'_>> = vec![];
+    for event in Parser::new(&text) {
+        // Show embedded HTML as text, unless it's trusted
+        let event = match event {
+            Event::Html(html) if !raw_html => Event::Text(html),
+            event => event,
+        };
+
+        match (events.last_mut(), event) {
+            (Some(Event::Text(prev)), Event::Text(text)) => {
+                *prev = format!("{}{}", prev, text).into();
+            }
+            (_, event) => events.push(event),
+        }
+    }
+
+    let headings = anchor_headings(&mut events);
+
+    // Don't autolink inside links or code, or touch image alt text at all
+    let mut in_link = false;
+    let mut in_image = false;
+
+    // Only touch text nodes, so we never mark up the inside of a tag or attribute
+    let events = events.into_iter().map(|event| match event {
+        Event::Start(Tag::Link(..)) | Event::Start(Tag::CodeBlock(_)) => {
+            in_link = true;
+            event
+        }
+        Event::End(Tag::Link(..)) | Event::End(Tag::CodeBlock(_)) => {
+            in_link = false;
+            event
+        }
+        Event::Start(Tag::Image(..)) => {
+            in_image = true;
+            event
+        }
+        Event::End(Tag::Image(..)) => {
+            in_image = false;
+            event
+        }
+        Event::Text(_) if in_image => event,
+        Event::Text(text) if in_link => Event::Html(highlight(&text, re).into()),
+        Event::Text(text) => Event::Html(spoilers(&text, re).into()),
+        event => event,
+    });
+
+    let mut output = String::new();
+    if headings.len() >= TOC_MIN_HEADINGS {
+        output += &toc(&headings);
+    }
+    html::push_html(&mut output, events);
+    output
+}
+
+/// Render markdown as readable plain text, without any markup.
+pub fn plain_text(text: &str) -> String {
+    let text = escape_spoilers(text);
+
+    let mut output = String::new();
+    let mut lists = vec![];
+    let mut link = None;
+    for event in Parser::new(&text) {
+        match event {
+            Event::Text(text) | Event::Code(text) => output += &text,
+            Event::SoftBreak => output += " ",
+            Event::HardBreak => output += "\n",
+            Event::Rule => output += "----\n\n",
+            Event::Start(Tag::List(start)) => {
+                if !lists.is_empty() && !output.ends_with('\n') {
+                    output += "\n";
+                }
+                lists.push(start);
+            }
+            Event::End(Tag::List(_)) => {
+                lists.pop();
+                if lists.is_empty() {
+                    output += "\n";
+                }
+            }
+            Event::Start(Tag::Item) => {
+                output += &"  ".repeat(lists.len().saturating_sub(1));
+                match lists.last_mut() {
+                    Some(Some(n)) => {
+                        output += &format!("{}. ", n);
+                        *n += 1;
+                    }
+                    _ => output += "- ",
+                }
+            }
+            Event::End(Tag::Item) if !output.ends_with('\n') => output += "\n",
+            Event::Start(Tag::Link(_, url, _)) => link = Some(url),
+            // Keep link targets, unless the text already shows them
+            Event::End(Tag::Link(..)) => {
+                if let Some(url) = link.take() {
+                    if !output.ends_with(&*url) {
+                        output += &format!(" <{}>", url);
+                    }
+                }
+            }
+            Event::End(Tag::Paragraph) | Event::End(Tag::Heading(_)) | Event::End(Tag::CodeBlock(_)) => {
+                output.truncate(output.trim_end_matches('\n').len());
+                output += if lists.is_empty() { "\n\n" } else { "\n" };
+            }
+            _ => {}
+        }
+    }
+
+    output.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn re(pattern: &str) -> Regex {
+        Regex::new(pattern).unwrap()
+    }
+
+    #[test]
+    fn highlight_outside_attributes() {
+        let html = markdown("Try [rust](https://www.rust-lang.org/) and Rust", Some(&re("(?i)rust")), false);
+        assert!(html.contains(r#"<a href="https://www.rust-lang.org/"><mark>rust</mark></a>"#));
+        assert!(html.contains("and <mark>Rust</mark>"));
+    }
+
+    #[test]
+    fn autolinks() {
+        let html = markdown("See https://www.rust-lang.org/learn. Or /r/rust and u/someone", None, false);
+        assert!(html.contains(r#"<a href="https://www.rust-lang.org/learn">https://www.rust-lang.org/learn</a>."#));
+        assert!(html.contains(r#"<a href="https://www.reddit.com/r/rust">/r/rust</a>"#));
+        assert!(html.contains(r#"<a href="https://www.reddit.com/user/someone">u/someone</a>"#));
+
+        // Not inside existing links or code
+        let html = markdown("[https://example.com](https://example.com) `/r/rust`", None, false);
+        assert_eq!(html.matches("<a ").count(), 1);
+        assert!(html.contains("<code>/r/rust</code>"));
+    }
+
+    #[test]
+    fn spoiler_markup() {
+        let html = markdown("The ending: >!he was dead!< all along", None, false);
+        assert!(html.contains(r#"<span class="spoiler" tabindex="0">he was dead</span> all along"#), "{}", html);
+        let html = markdown(">!at the start!<", None, false);
+        assert!(html.contains(r#"<span class="spoiler" tabindex="0">at the start</span>"#), "{}", html);
+        assert!(!html.contains("<blockquote>"));
+
+        let html = markdown("```\nlet x = a >!b!< c;\n```", None, false);
+        assert!(!html.contains("spoiler"), "{}", html);
+        assert!(html.contains("a &gt;!b!&lt; c"), "{}", html);
+    }
+
+    #[test]
+    fn heading_anchors() {
+        let html = markdown("## Getting started\n\nText\n\n## Next steps\n\n## Getting Started!", None, true);
+        for id in ["getting-started", "next-steps", "getting-started-2"] {
+            assert!(html.contains(&format!(r#"<h2 id="{}">"#, id)), "{}", html);
+            assert!(html.contains(&format!(r##"<a href="#{}">"##, id)), "{}", html);
+        }
+        assert!(html.starts_with(r#"<details class="toc">"#));
+        assert_eq!(html.matches("<li class=\"toc-2\">").count(), 3);
+
+        let html = markdown("## One\n\n## Two", None, true);
+        assert!(!html.contains("toc"));
+        assert!(html.contains(r#"<h2 id="two">"#));
+    }
+
+    #[test]
+    fn nested_quotes() {
+        let html = markdown("> outer\n>> inner\n\nafter", None, false);
+        let compact: String = html.split_whitespace().collect();
+        assert!(compact.contains("<blockquote><p>outer</p><blockquote><p>inner</p></blockquote></blockquote><p>after</p>"), "{}", html);
+    }
+
+    #[test]
+    fn quote_paragraphs() {
+        let html = markdown("> first\n\n> second\n\nafter", None, false);
+        let compact: String = html.split_whitespace().collect();
+        assert!(compact.contains("<blockquote><p>first</p><p>second</p></blockquote><p>after</p>"), "{}", html);
+        assert_eq!(html.matches("<blockquote>").count(), 1);
+
+        let html = markdown("> quote\n\n>!spoiler!<", None, false);
+        assert!(html.contains(r#"<span class="spoiler" tabindex="0">spoiler</span>"#), "{}", html);
+    }
+
+    #[test]
+    fn snippet_fragments() {
+        let filler = "lorem ipsum ".repeat(20);
+        let text = format!("{}rust is great{}and rust again{}", filler, filler, filler);
+        let re = re("(?i)rust");
+
+        let one = snippet(&text, Some(&re), 40, 1);
+        assert_eq!(one.matches("<mark>rust</mark>").count(), 1);
+
+        let two = snippet(&text, Some(&re), 40, 2);
+        assert_eq!(two.matches("<mark>rust</mark>").count(), 2);
+        assert!(two.starts_with('…') && two.ends_with('…'));
+        assert_eq!(two.matches('…').count(), 3, "{}", two);
+    }
+
+    #[test]
+    fn raw_html() {
+        let text = "# Video\n\n<iframe src=\"https://example.com/\"></iframe>";
+        let html = markdown(text, None, false);
+        assert!(html.contains(r#"<h1 id="video">Video</h1>"#), "{}", html);
+        assert!(html.contains("&lt;iframe"), "{}", html);
+        assert!(!html.contains("<iframe"));
+
+        let html = markdown(text, None, true);
+        assert!(html.contains(r#"<h1 id="video">Video</h1>"#), "{}", html);
+        assert!(html.contains(r#"<iframe src="https://example.com/"></iframe>"#), "{}", html);
+    }
+
+    #[test]
+    fn counts() {
+        assert_eq!(format_count(0, "en-US"), "0");
+        assert_eq!(format_count(999, "en-US"), "999");
+        assert_eq!(format_count(12340, "en-US"), "12,340");
+        assert_eq!(format_count(1234567, "en"), "1,234,567");
+        assert_eq!(format_count(12340, "de-DE"), "12.340");
+        assert_eq!(format_count(12340, "fr_FR"), "12\u{a0}340");
+    }
+
+    #[test]
+    fn sanitize() {
+        let html = r#"<div class="md"><p>Hi <strong onclick="x()">there</strong> <a href="javascript:alert(1)">x</a> <a href='https://rust-lang.org'>y</a></p><!-- note --><script>alert("<p>")</script><img src=x onerror=y>&amp;lt;</div>"#;
+        assert_eq!(
+            sanitize_html(html, None),
+            r#"<div><p>Hi <strong>there</strong> <a>x</a> <a href="https://rust-lang.org">y</a></p>&amp;lt;</div>"#,
+        );
+        assert_eq!(sanitize_html("<p>a <b>rust</b> job</p>", Some(&re("rust"))), "<p>a <mark>rust</mark> job</p>");
+    }
+}
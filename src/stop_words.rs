@@ -0,0 +1,65 @@
+//! Common words that carry little meaning on their own.
+
+/// English stop words.
+const ENGLISH: &[&str] = &[
+    "a", "about", "after", "all", "also", "am", "an", "and", "any", "are", "as", "at",
+    "be", "because", "been", "but", "by",
+    "can", "could",
+    "did", "do", "does", "don", "for", "from",
+    "get", "got",
+    "had", "has", "have", "he", "her", "him", "his", "how",
+    "i", "if", "in", "into", "is", "it", "its",
+    "just",
+    "like",
+    "m", "me", "more", "my",
+    "no", "not", "now",
+    "of", "on", "one", "or", "other", "our", "out",
+    "re", "s", "she", "should", "so", "some",
+    "t", "than", "that", "the", "their", "them", "then", "there", "these", "they", "this", "to",
+    "up", "us",
+    "ve", "very",
+    "was", "we", "were", "what", "when", "which", "who", "will", "with", "would",
+    "you", "your",
+];
+
+/// Spanish stop words.
+const SPANISH: &[&str] = &[
+    "a", "al", "algo", "como", "con", "de", "del", "donde", "el", "ella", "ellos", "en",
+    "era", "es", "esa", "ese", "esta", "este", "fue", "ha", "hay", "la", "las", "le",
+    "les", "lo", "los", "mas", "me", "mi", "muy", "ni", "no", "nos", "o", "para", "pero",
+    "por", "que", "se", "sin", "sobre", "son", "su", "sus", "te", "tu", "un", "una",
+    "uno", "y", "ya", "yo",
+];
+
+/// German stop words.
+const GERMAN: &[&str] = &[
+    "aber", "als", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis", "da", "das",
+    "dass", "dem", "den", "der", "des", "die", "doch", "du", "ein", "eine", "einem",
+    "einen", "einer", "er", "es", "für", "hat", "ich", "ihr", "im", "in", "ist", "ja",
+    "kann", "mit", "nach", "nicht", "noch", "nur", "oder", "sich", "sie", "sind", "so",
+    "und", "uns", "von", "war", "was", "wie", "wir", "zu", "zum", "zur",
+];
+
+/// French stop words.
+const FRENCH: &[&str] = &[
+    "a", "au", "aux", "avec", "ce", "ces", "dans", "de", "des", "du", "elle", "en", "est",
+    "et", "il", "ils", "je", "la", "le", "les", "leur", "lui", "ma", "mais", "me", "mes",
+    "mon", "ne", "nous", "on", "ou", "par", "pas", "pour", "qu", "que", "qui", "sa", "se",
+    "ses", "son", "sur", "ta", "te", "tu", "un", "une", "vous", "y",
+];
+
+/// Get the stop words for a language code, like "en".
+pub fn for_lang(lang: &str) -> Option<&'static [&'static str]> {
+    match lang {
+        "en" => Some(ENGLISH),
+        "es" => Some(SPANISH),
+        "de" => Some(GERMAN),
+        "fr" => Some(FRENCH),
+        _ => None,
+    }
+}
+
+/// Check whether a (lowercase) word is an English stop word.
+pub fn is_stop_word(word: &str) -> bool {
+    ENGLISH.contains(&word)
+}
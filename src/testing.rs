@@ -0,0 +1,101 @@
+//! Helpers for tests.
+
+use crate::build;
+use crate::config::Config;
+use crate::posts::Corpus;
+
+use rocket::local::blocking::Client;
+
+use serde_json::{Value, json};
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A temporary directory, removed when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let n = COUNT.fetch_add(1, Ordering::Relaxed);
+        let path = env::temp_dir().join(format!("pheddit-test-{}-{}", process::id(), n));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Write some posts to a file in this directory, one per line.
+    pub fn write_posts(&self, name: &str, posts: &[Value]) -> PathBuf {
+        let text: String = posts.iter().map(|post| post.to_string() + "\n").collect();
+        let path = self.0.join(name);
+        fs::write(&path, text).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The default configuration, with labels written somewhere harmless.
+pub fn config() -> Config {
+    let mut config = Config::from_env();
+    config.labels = env::temp_dir().join(format!("pheddit-test-{}-labels.jsonl", process::id()));
+    config
+}
+
+/// Load some posts into a corpus.
+pub fn corpus(posts: &[Value]) -> Arc<Corpus> {
+    let dir = TempDir::new();
+    dir.write_posts("posts.json", posts);
+    Arc::new(Corpus::new(vec![dir.path().to_path_buf()]))
+}
+
+/// A client for a server with a corpus and a configuration.
+pub fn serve(corpus: Arc<Corpus>, config: Config) -> Client {
+    let figment = rocket::Config::figment().merge(("log_level", "off"));
+    Client::tracked(build(figment, corpus, config)).unwrap()
+}
+
+/// A client for a server with some posts and a configuration.
+pub fn client_with(posts: &[Value], config: Config) -> Client {
+    serve(corpus(posts), config)
+}
+
+/// A client for a server with some posts and the default configuration.
+pub fn client(posts: &[Value]) -> Client {
+    client_with(posts, config())
+}
+
+/// Fetch a page's body.
+pub fn get(client: &Client, uri: &str) -> String {
+    client.get(uri.to_string()).dispatch().into_string().unwrap_or_default()
+}
+
+/// A minimal post.
+pub fn fixture(id: &str, title: &str, selftext: &str) -> Value {
+    json!({"id": id, "title": title, "selftext": selftext})
+}
+/// Fetch a JSON response.
+pub fn get_json(client: &Client, uri: &str) -> Value {
+    serde_json::from_str(&get(client, uri)).unwrap()
+}
+
+/// The IDs of the results of an /api/search query, in order.
+pub fn search_ids(client: &Client, params: &str) -> Vec<String> {
+    let json = get_json(client, &format!("/api/search?{}", params));
+    json["results"].as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["id"].as_str().unwrap().to_string())
+        .collect()
+}